@@ -1,12 +1,13 @@
 use std::{collections::HashMap, path::PathBuf};
 
 pub use crate::update::Message;
-use crate::{FileItem, ReplaceItem, SetUi};
+use crate::{FileItem, SetUi, StepItem, StepKind};
 use iced::{executor, widget::scrollable, Application, Command, Theme};
 
 use error_log::{ErrorLogAnyhow, FormatMode};
 use iced_aw::Modal;
 use renameplus::{rename::Rename, Config};
+use snake_helper::unwrap_or_print_err;
 
 #[derive(Debug, Default)]
 pub struct RenamePlusGui {
@@ -14,7 +15,8 @@ pub struct RenamePlusGui {
 	pub data: Rename,
 	pub files: Vec<FileItem>,
 	pub hovered: Option<PathBuf>,
-	pub replace_ui: Vec<ReplaceItem>,
+	/// Changes to the names, applied from top to bottom.
+	pub steps: Vec<StepItem>,
 	pub sets: HashMap<String, SetUi>,
 	pub new_set: SetUi,
 	pub sets_overlay: bool,
@@ -38,14 +40,14 @@ impl Application for RenamePlusGui {
 				err_log.display_unwrap_or_default()
 			};
 			Self {
-				data: Rename {
-					config,
-					..Default::default()
-				},
+				data: unwrap_or_print_err!(
+					Rename::builder().config(config).build(),
+					Rename::default()
+				),
 				..Default::default()
 			}
 		};
-		out.new_replace();
+		out.steps.push(StepItem::new(StepKind::Replace));
 		out.reload_sets();
 		(out, Command::none())
 	}
@@ -4,23 +4,11 @@ use anyhow::Result;
 use log::error;
 use native_dialog::{FileDialog, MessageDialog};
 
-use crate::{RenamePlusGui, ReplaceItem, SetUi};
+use crate::{RenamePlusGui, SetUi};
 
 impl RenamePlusGui {
 	pub(super) fn any_changes(&mut self) {
-		self.changes = self
-			.data
-			.prefix
-			.as_ref()
-			.map(|p| !p.is_empty())
-			.unwrap_or(false)
-			|| self
-				.data
-				.suffix
-				.as_ref()
-				.map(|s| !s.is_empty())
-				.unwrap_or(false)
-			|| self.data.replace.iter().any(|r| !r.0.is_empty());
+		self.changes = !self.data.steps.is_empty();
 	}
 	pub(super) fn folder_ask(&mut self, new_files: &mut Vec<PathBuf>) {
 		match FileDialog::new().show_open_multiple_file() {
@@ -44,12 +32,6 @@ impl RenamePlusGui {
 			Err(e) => error!("Failed to get path: {}", e),
 		}
 	}
-	// fn new_replace(&mut self, search: impl Into<String>, replace: impl Into<String>) {
-	pub(super) fn new_replace(&mut self) {
-		self.data.push_replace("".to_string(), "".to_string());
-		// let last = self.data.replace.last().unwrap();
-		self.replace_ui.push(ReplaceItem::new("", ""));
-	}
 	pub(super) fn reload_sets(&mut self) {
 		self.sets.clear();
 		for (name, set) in self.data.config.sets.iter() {
@@ -58,8 +40,9 @@ impl RenamePlusGui {
 		}
 	}
 	pub(super) fn update_previews(&mut self) -> Result<()> {
-		for file in self.files.iter_mut() {
-			file.update_preview(&self.data)?
+		self.data.files = self.files.iter().map(|f| f.path.clone()).collect();
+		for (file, (_, new)) in self.files.iter_mut().zip(self.data.preview_results()) {
+			file.update_preview(new)?
 		}
 		Ok(())
 	}
@@ -1,4 +1,4 @@
-use crate::RenamePlusGui;
+use crate::{RenamePlusGui, StepItem};
 
 impl RenamePlusGui {
 	pub fn do_rename(&self) {
@@ -11,6 +11,10 @@ impl RenamePlusGui {
 		if self.files.is_empty() {
 			out.push_str("Please add least one path.\n");
 		}
+		for e in self.steps.iter().filter_map(StepItem::error) {
+			out.push_str(e);
+			out.push('\n');
+		}
 		if !self.changes {
 			out.push_str("Nothing to Change\n");
 		}
@@ -11,22 +11,24 @@ use itertools::Itertools;
 use native_dialog::{FileDialog, MessageDialog, MessageType};
 use snake_helper::{unwrap_or_print_err, unwrap_some_or};
 
-use crate::{FileItem, FileMessage, RenamePlusGui, ReplaceMessage, SetUiMessage};
+use crate::{
+	update_numbering, FileItem, FileMessage, NumberingMessage, RenamePlusGui, SetUiMessage,
+	StepItem, StepKind, StepMessage,
+};
 
 #[derive(Debug, Clone)]
 pub enum Message {
 	AddPaths,
 	Event(Event),
 	FileMessage(usize, FileMessage),
-	PrefixChanged(String),
 	RemoveOutputDir,
 	Run,
 	SelectOutputDir,
-	SuffixChanged(String),
 	ToggleCopy(bool),
 	ToggleDirs(bool),
-	AddReplace,
-	ReplaceMessage(usize, ReplaceMessage),
+	AddStep(StepKind),
+	StepMessage(usize, StepMessage),
+	NumberingMessage(NumberingMessage),
 	ShowSetsSelect,
 	HideSetsSelect,
 	SetMessage(String, SetUiMessage),
@@ -84,14 +86,6 @@ impl RenamePlusGui {
 			Message::Event(Event::Window(WinEvent::FileHovered(p))) => self.hovered = Some(p),
 			// Reset hovered path
 			Message::Event(Event::Window(WinEvent::FilesHoveredLeft)) => self.hovered = None,
-			Message::PrefixChanged(p) => {
-				self.data.prefix = Some(p);
-				changed = true
-			}
-			Message::SuffixChanged(p) => {
-				self.data.suffix = Some(p);
-				changed = true
-			}
 			Message::Run => self.do_rename(),
 			Message::ToggleDirs(a) => self.data.dirs = a,
 			Message::ToggleCopy(c) => self.data.copy = c,
@@ -108,47 +102,54 @@ impl RenamePlusGui {
 				}
 				// Remove files removed by user
 				self.files.retain_mut(|file| !file.deleted());
+				changed = true
 			}
 			// Ignore all others events
 			Message::Event(_) => (),
-			Message::AddReplace => self.new_replace(),
-			Message::ReplaceMessage(i, msg) => {
-				self.update_replace(i, msg);
+			Message::AddStep(kind) => {
+				self.steps.push(StepItem::new(kind));
+				changed = true
+			}
+			Message::StepMessage(i, msg) => {
+				self.update_step(i, msg);
+				changed = true
+			}
+			Message::NumberingMessage(msg) => {
+				update_numbering(&mut self.data.numbering, msg);
 				changed = true
 			}
 			Message::ShowSetsSelect => self.sets_overlay = true,
 			Message::HideSetsSelect => self.sets_overlay = false,
 		}
 		if !new_files.is_empty() {
-			let mut new: Vec<FileItem> = vec![];
-			for i in 0..new_files.len() {
-				let file: PathBuf = new_files.remove(i);
-				let new_path: PathBuf = unwrap_or_print_err!(self.data.get_new_path(&file), None)
-					.unwrap_or(file.clone());
-				new.push(FileItem::new(file, new_path));
-			}
+			// The previews are filled in below.
+			let mut new: Vec<FileItem> = new_files
+				.into_iter()
+				.map(|file| FileItem::new(file.clone(), file))
+				.collect();
 			self.files.append(&mut new);
-			self.files = self.files.clone().into_iter().unique().collect()
+			self.files = self.files.clone().into_iter().unique().collect();
+			// Counters depend on the position of all files.
+			changed = true
 		}
 		if changed {
+			self.data.steps = self.steps.iter().filter_map(StepItem::to_step).collect();
 			self.any_changes();
 			self.update_previews()?;
 		};
 		Ok(err_log)
 	}
-	pub(super) fn update_replace(&mut self, i: usize, msg: ReplaceMessage) {
+	pub(super) fn update_step(&mut self, i: usize, msg: StepMessage) {
 		match msg {
-			ReplaceMessage::ChangeSearch(s) => {
-				self.data.replace[i].0 = s.clone();
-				self.replace_ui[i].search = s
-			}
-			ReplaceMessage::ChangeReplace(r) => {
-				self.data.replace[i].1 = r.clone();
-				self.replace_ui[i].replace = r
+			StepMessage::Up if i > 0 => self.steps.swap(i, i - 1),
+			StepMessage::Down if i + 1 < self.steps.len() => self.steps.swap(i, i + 1),
+			StepMessage::Delete => {
+				self.steps.remove(i);
 			}
-			ReplaceMessage::Delete => {
-				self.data.replace.remove(i);
-				self.replace_ui.remove(i);
+			msg => {
+				if let Some(step) = self.steps.get_mut(i) {
+					step.update(msg)
+				}
 			}
 		}
 	}
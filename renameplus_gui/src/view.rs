@@ -1,10 +1,10 @@
 use iced::{
-	widget::{button, row, text, text_input, toggler, tooltip, Column, Container},
+	widget::{button, pick_list, row, text, toggler, Column, Container},
 	Color, Element,
 };
 use iced_aw::Card;
 
-use crate::{col, Message, PresetDefault, RenamePlusGui};
+use crate::{col, numbering_view, Message, PresetDefault, RenamePlusGui, StepItem, StepKind};
 
 impl RenamePlusGui {
 	pub fn view_overlay(&self) -> Element<'_, Message> {
@@ -57,17 +57,22 @@ impl RenamePlusGui {
 			}
 			out
 		};
-		let replace: Column<Message> = {
+		let steps: Column<Message> = {
 			let mut out = Column::new();
-			for (i, rename) in self.replace_ui.iter().enumerate() {
-				out = out.push(
-					rename
-						.view()
-						.map(move |msg| Message::ReplaceMessage(i, msg)),
-				);
+			for (i, step) in self.steps.iter().enumerate() {
+				out = out.push(step.view().map(move |msg| Message::StepMessage(i, msg)));
 			}
 			out
 		};
+		// The numbering is only used by templates.
+		let numbering: Element<Message> = match self
+			.steps
+			.iter()
+			.any(|step| matches!(step, StepItem::Template { .. }))
+		{
+			true => numbering_view(&self.data.numbering).map(Message::NumberingMessage),
+			false => Column::new().into(),
+		};
 		let drop_hint: String = match self.hovered {
 			Some(ref f) => format!("Drop file to add path(s): {}", f.display()),
 			None => String::from("\n"),
@@ -92,30 +97,6 @@ impl RenamePlusGui {
 					button(text("X")).on_press(Message::RemoveOutputDir)
 				]
 				.preset_default(),
-				tooltip(
-					text_input(
-						"PREFIX",
-						match self.data.prefix {
-							Some(ref s) => s,
-							None => "",
-						},
-					)
-					.on_input(Message::PrefixChanged),
-					"Text to add before file names",
-					tooltip::Position::Right,
-				),
-				tooltip(
-					text_input(
-						"SUFFIX",
-						match self.data.suffix {
-							Some(ref s) => s,
-							None => "",
-						},
-					)
-					.on_input(Message::SuffixChanged),
-					"Text to add after file names",
-					tooltip::Position::Right,
-				),
 				toggler(
 					"Allow directories".to_string(),
 					self.data.dirs,
@@ -127,8 +108,9 @@ impl RenamePlusGui {
 					Message::ToggleCopy
 				),
 				files,
-				replace,
-				button(text("Add replace filter")).on_press(Message::AddReplace),
+				steps,
+				numbering,
+				pick_list(&StepKind::ALL[..], None, Message::AddStep).placeholder("Add step"),
 				button(text("Select Sets")).on_press(Message::ShowSetsSelect),
 				run_button,
 				validate_msgs,
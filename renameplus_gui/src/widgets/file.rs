@@ -5,7 +5,6 @@ use iced::{
 	widget::{button, row, text},
 	Element,
 };
-use renameplus::rename::Rename;
 
 #[derive(Default, Debug, Clone)]
 pub struct FileList(pub Vec<FileItem>);
@@ -27,10 +26,9 @@ impl FileItem {
 	pub fn deleted(&self) -> bool {
 		self.deleted
 	}
-	pub fn update_preview(&mut self, rename: &Rename) -> Result<()> {
-		self.new_path = rename
-			.get_new_path(&self.path)?
-			.unwrap_or(self.path.clone());
+	/// Show `new`, the result of [`renameplus::Rename::preview_results`] for this file.
+	pub fn update_preview(&mut self, new: Result<Option<PathBuf>>) -> Result<()> {
+		self.new_path = new?.unwrap_or(self.path.clone());
 		Ok(())
 	}
 	pub fn new(path: PathBuf, new_path: PathBuf) -> Self {
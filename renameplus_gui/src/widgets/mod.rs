@@ -1,7 +1,11 @@
 mod file;
+mod numbering;
 mod replace;
 mod set;
+mod step;
 
 pub use file::*;
+pub use numbering::*;
 pub use replace::*;
 pub use set::*;
+pub use step::*;
@@ -0,0 +1,74 @@
+use std::str::FromStr;
+
+use iced::{
+	widget::{pick_list, row, text, text_input, toggler, tooltip},
+	Element,
+};
+use renameplus::{Numbering, SortBy};
+
+use crate::PresetDefault;
+
+#[derive(Debug, Clone)]
+pub enum NumberingMessage {
+	ChangeStart(String),
+	ChangeStep(String),
+	ChangePad(String),
+	ChangeSort(SortBy),
+	TogglePerDir(bool),
+}
+
+pub fn update_numbering(numbering: &mut Numbering, msg: NumberingMessage) {
+	match msg {
+		NumberingMessage::ChangeStart(s) => set_number(&mut numbering.start, &s),
+		NumberingMessage::ChangeStep(s) => set_number(&mut numbering.step, &s),
+		NumberingMessage::ChangePad(s) => set_number(&mut numbering.pad, &s),
+		NumberingMessage::ChangeSort(sort) => numbering.sort = sort,
+		NumberingMessage::TogglePerDir(per_dir) => numbering.per_dir = per_dir,
+	}
+}
+
+/// Keep the old value for input which isn't a number, an empty field counts as zero.
+fn set_number<T: FromStr + Default>(number: &mut T, input: &str) {
+	match input.is_empty() {
+		true => *number = T::default(),
+		false => {
+			if let Ok(n) = input.parse() {
+				*number = n
+			}
+		}
+	}
+}
+
+pub fn numbering_view(numbering: &Numbering) -> Element<'_, NumberingMessage> {
+	row![
+		text("Numbering"),
+		tooltip(
+			text_input("START", &numbering.start.to_string())
+				.on_input(NumberingMessage::ChangeStart),
+			"First value of {n}",
+			tooltip::Position::Bottom,
+		),
+		tooltip(
+			text_input("STEP", &numbering.step.to_string()).on_input(NumberingMessage::ChangeStep),
+			"Added to {n} for every file",
+			tooltip::Position::Bottom,
+		),
+		tooltip(
+			text_input("PAD", &numbering.pad.to_string()).on_input(NumberingMessage::ChangePad),
+			"Minimum amount of digits, padded with zeros",
+			tooltip::Position::Bottom,
+		),
+		pick_list(
+			&SortBy::ALL[..],
+			Some(numbering.sort),
+			NumberingMessage::ChangeSort
+		),
+		toggler(
+			"Restart in every directory".to_string(),
+			numbering.per_dir,
+			NumberingMessage::TogglePerDir
+		),
+	]
+	.preset_default()
+	.into()
+}
@@ -1,5 +1,5 @@
 use iced::{
-	widget::{button, row, text, text_input},
+	widget::{checkbox, row, text_input},
 	Element,
 };
 
@@ -9,6 +9,7 @@ use crate::PresetDefault;
 pub struct ReplaceItem {
 	pub replace: String,
 	pub search: String,
+	pub regex: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -16,7 +17,7 @@ pub enum ReplaceMessage {
 	ChangeSearch(String),
 	// AddSearch(),
 	ChangeReplace(String),
-	Delete,
+	ToggleRegex(bool),
 }
 
 impl ReplaceItem {
@@ -24,6 +25,14 @@ impl ReplaceItem {
 		Self {
 			search: search.into(),
 			replace: replace.into(),
+			regex: false,
+		}
+	}
+	pub fn update(&mut self, msg: ReplaceMessage) {
+		match msg {
+			ReplaceMessage::ChangeSearch(s) => self.search = s,
+			ReplaceMessage::ChangeReplace(r) => self.replace = r,
+			ReplaceMessage::ToggleRegex(b) => self.regex = b,
 		}
 	}
 	pub fn view(&self) -> Element<'static, ReplaceMessage> {
@@ -31,7 +40,7 @@ impl ReplaceItem {
 			text_input("SEARCH", &self.search).on_input(ReplaceMessage::ChangeSearch),
 			// tooltip(button(text("+")), "Add search", Position::FollowCursor),
 			text_input("REPLACE", &self.replace).on_input(ReplaceMessage::ChangeReplace),
-			button(text("X")).on_press(ReplaceMessage::Delete)
+			checkbox("Regex", self.regex, ReplaceMessage::ToggleRegex),
 		]
 		.preset_default()
 		.into()
@@ -0,0 +1,162 @@
+use std::fmt::Display;
+
+use iced::{
+	widget::{button, pick_list, row, text, text_input, tooltip, Row},
+	Element,
+};
+use renameplus::{Case, CaseConversion, CaseTarget, Replace, Step, Template, WordBoundary};
+
+use crate::{PresetDefault, ReplaceItem, ReplaceMessage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+	Prefix,
+	Suffix,
+	Replace,
+	Case,
+	Template,
+}
+
+#[derive(Debug)]
+pub enum StepItem {
+	Prefix(String),
+	Suffix(String),
+	Replace(ReplaceItem),
+	Case(CaseConversion),
+	Template { draft: String, err: Option<String> },
+}
+
+#[derive(Debug, Clone)]
+pub enum StepMessage {
+	ChangeText(String),
+	Replace(ReplaceMessage),
+	ChangeCase(Case),
+	ChangeCaseTarget(CaseTarget),
+	ChangeWordBoundary(WordBoundary),
+	Up,
+	Down,
+	Delete,
+}
+
+impl StepKind {
+	pub const ALL: [Self; 5] = [
+		Self::Prefix,
+		Self::Suffix,
+		Self::Replace,
+		Self::Case,
+		Self::Template,
+	];
+}
+
+impl Display for StepKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Prefix => "Prefix",
+			Self::Suffix => "Suffix",
+			Self::Replace => "Replace",
+			Self::Case => "Change case",
+			Self::Template => "Template",
+		})
+	}
+}
+
+impl StepItem {
+	pub fn new(kind: StepKind) -> Self {
+		match kind {
+			StepKind::Prefix => Self::Prefix(String::new()),
+			StepKind::Suffix => Self::Suffix(String::new()),
+			StepKind::Replace => Self::Replace(ReplaceItem::new("", "")),
+			StepKind::Case => Self::Case(CaseConversion::default()),
+			StepKind::Template => Self::Template {
+				draft: String::new(),
+				err: None,
+			},
+		}
+	}
+	/// Step to run, [`None`] if it wouldn't change anything or is invalid.
+	pub fn to_step(&self) -> Option<Step> {
+		match self {
+			Self::Prefix(text) if !text.is_empty() => Some(Step::Prefix { text: text.clone() }),
+			Self::Suffix(text) if !text.is_empty() => Some(Step::Suffix { text: text.clone() }),
+			Self::Replace(r) if !r.search.is_empty() => Some(Step::Replace(match r.regex {
+				true => Replace::new_regex(&r.search, &r.replace),
+				false => Replace::new(&r.search, &r.replace),
+			})),
+			Self::Case(c) => Some(Step::Case(*c)),
+			Self::Template { draft, err: None } if !draft.is_empty() => Template::parse(draft)
+				.ok()
+				.map(|template| Step::Template { template }),
+			_ => None,
+		}
+	}
+	pub fn error(&self) -> Option<&str> {
+		match self {
+			Self::Template { err, .. } => err.as_deref(),
+			_ => None,
+		}
+	}
+	pub fn update(&mut self, msg: StepMessage) {
+		match (self, msg) {
+			(Self::Prefix(text) | Self::Suffix(text), StepMessage::ChangeText(t)) => *text = t,
+			(Self::Template { draft, err }, StepMessage::ChangeText(t)) => {
+				*err = match t.is_empty() {
+					true => None,
+					false => Template::parse(&t).err().map(|e| format!("{e:#}")),
+				};
+				*draft = t;
+			}
+			(Self::Replace(r), StepMessage::Replace(msg)) => r.update(msg),
+			(Self::Case(c), StepMessage::ChangeCase(case)) => c.case = case,
+			(Self::Case(c), StepMessage::ChangeCaseTarget(target)) => c.target = target,
+			(Self::Case(c), StepMessage::ChangeWordBoundary(boundary)) => c.boundary = boundary,
+			// Moving and deleting is handled by the list.
+			_ => (),
+		}
+	}
+	pub fn view(&self) -> Element<'_, StepMessage> {
+		let edit: Element<'_, StepMessage> = match self {
+			Self::Prefix(p) => tooltip(
+				text_input("PREFIX", p).on_input(StepMessage::ChangeText),
+				"Text to add before file names",
+				tooltip::Position::Right,
+			)
+			.into(),
+			Self::Suffix(s) => tooltip(
+				text_input("SUFFIX", s).on_input(StepMessage::ChangeText),
+				"Text to add after file names",
+				tooltip::Position::Right,
+			)
+			.into(),
+			Self::Replace(r) => r.view().map(StepMessage::Replace),
+			Self::Case(c) => row![
+				text("Case"),
+				pick_list(&Case::ALL[..], Some(c.case), StepMessage::ChangeCase),
+				pick_list(
+					&CaseTarget::ALL[..],
+					Some(c.target),
+					StepMessage::ChangeCaseTarget
+				),
+				pick_list(
+					&WordBoundary::ALL[..],
+					Some(c.boundary),
+					StepMessage::ChangeWordBoundary
+				),
+			]
+			.preset_default()
+			.into(),
+			Self::Template { draft, .. } => tooltip(
+				text_input("TEMPLATE", draft).on_input(StepMessage::ChangeText),
+				"Build new names from placeholders like {stem}, {ext}, {n:3} or {mtime:%Y-%m-%d}",
+				tooltip::Position::Right,
+			)
+			.into(),
+		};
+		Row::new()
+			.push(edit)
+			.push(button(text("^")).on_press(StepMessage::Up))
+			.push(button(text("v")).on_press(StepMessage::Down))
+			.push(button(text("X")).on_press(StepMessage::Delete))
+			.preset_default()
+			.into()
+	}
+}
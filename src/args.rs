@@ -1,9 +1,13 @@
 use std::path::PathBuf;
 
+use crate::case::{Case, CaseTarget, WordBoundary};
+use crate::numbering::SortBy;
+use crate::preserve::Attribute;
 use clap::{
 	builder::EnumValueParser, builder::PossibleValue, command, value_parser, Arg, ArgAction,
-	ArgMatches, ValueHint,
+	ArgGroup, ArgMatches, Command, ValueHint,
 };
+use serde::{Deserialize, Serialize};
 
 pub fn matches() -> ArgMatches {
 	command!()
@@ -16,17 +20,124 @@ pub fn matches() -> ArgMatches {
 				.required(false)
 				.help_heading("SIMPLE")
 				.value_hint(ValueHint::Other)
+				.action(ArgAction::Append)
 				.help("Prefix to be added to the file"),
 		)
+		.arg(
+			Arg::new("case")
+				.long("case")
+				.value_parser(EnumValueParser::<Case>::new())
+				.help_heading("CASE")
+				.action(ArgAction::Append)
+				.help("Change the case of the name"),
+		)
+		.arg(
+			Arg::new("case-target")
+				.long("case-target")
+				.value_parser(EnumValueParser::<CaseTarget>::new())
+				.help_heading("CASE")
+				.default_value("stem")
+				.requires("case")
+				.help("Part of the name to change the case of"),
+		)
+		.arg(
+			Arg::new("word-boundary")
+				.long("word-boundary")
+				.value_parser(EnumValueParser::<WordBoundary>::new())
+				.help_heading("CASE")
+				.default_value("separator")
+				.requires("case")
+				.help("Where words start for --case"),
+		)
+		.arg(
+			Arg::new("template")
+				.long("template")
+				.short('t')
+				.value_parser(value_parser!(String))
+				.value_name("TEMPLATE")
+				.help_heading("SIMPLE")
+				.value_hint(ValueHint::Other)
+				.action(ArgAction::Append)
+				.help("Build the new name from placeholders, e.g. \"{parent}_{n:3}.{ext}\""),
+		)
+		.arg(
+			Arg::new("number-start")
+				.long("number-start")
+				.value_parser(value_parser!(u64))
+				.value_name("N")
+				.help_heading("NUMBERING")
+				.default_value("1")
+				.help("First value of {n}"),
+		)
+		.arg(
+			Arg::new("number-step")
+				.long("number-step")
+				.value_parser(value_parser!(u64))
+				.value_name("N")
+				.help_heading("NUMBERING")
+				.default_value("1")
+				.help("Increment of {n} between files"),
+		)
+		.arg(
+			Arg::new("number-pad")
+				.long("number-pad")
+				.value_parser(value_parser!(usize))
+				.value_name("DIGITS")
+				.help_heading("NUMBERING")
+				.default_value("0")
+				.help("Pad {n} with zeros to this amount of digits"),
+		)
+		.arg(
+			Arg::new("sort")
+				.long("sort")
+				.value_parser(EnumValueParser::<SortBy>::new())
+				.help_heading("NUMBERING")
+				.default_value("args")
+				.help("Order in which files are numbered"),
+		)
+		.arg(
+			Arg::new("per-dir")
+				.long("per-dir")
+				.action(ArgAction::SetTrue)
+				.help_heading("NUMBERING")
+				.help("Restart numbering in every directory"),
+		)
 		.arg(
 			Arg::new("file")
 				.value_parser(value_parser!(PathBuf))
 				.value_name("FILE")
 				.value_hint(ValueHint::AnyPath)
-				.required(true)
+				.required_unless_present_any(["swap", "from-stdin", "from-file"])
 				.action(ArgAction::Append)
 				.help("File(s)  to be renamed"),
 		)
+		.arg(
+			Arg::new("from-stdin")
+				.long("from-stdin")
+				.action(ArgAction::SetTrue)
+				.conflicts_with("from-file")
+				.help_heading("INPUT")
+				.help("Read the files to rename from stdin, one per line"),
+		)
+		.arg(
+			Arg::new("from-file")
+				.long("from-file")
+				.value_name("PATH")
+				.value_parser(value_parser!(PathBuf))
+				.value_hint(ValueHint::FilePath)
+				.help_heading("INPUT")
+				.help("Read the files to rename from this file, one per line"),
+		)
+		.group(ArgGroup::new("file-list").args(["from-stdin", "from-file"]))
+		.arg(
+			Arg::new("null")
+				.long("null")
+				.short('0')
+				.action(ArgAction::SetTrue)
+				.requires("file-list")
+				.help_heading("INPUT")
+				.help("Files are separated by NUL instead of newlines, like from 'find -print0'"),
+		)
 		.arg(
 			Arg::new("dry")
 				.long("dry")
@@ -43,6 +154,68 @@ pub fn matches() -> ArgMatches {
 				.action(ArgAction::SetTrue)
 				.help("Allow renaming of directories"),
 		)
+		.arg(
+			Arg::new("preserve")
+				.long("preserve")
+				.value_name("ATTRIBUTES")
+				.value_parser(EnumValueParser::<Attribute>::new())
+				.value_delimiter(',')
+				.action(ArgAction::Append)
+				.help_heading("GENERAL")
+				.help("Metadata to keep with --copy, moves keep all of it"),
+		)
+		.arg(
+			Arg::new("recursive")
+				.long("recursive")
+				.help_heading("RECURSIVE")
+				.action(ArgAction::SetTrue)
+				.help("Rename the contents of directories, deepest first"),
+		)
+		.arg(
+			Arg::new("include")
+				.long("include")
+				.value_name("GLOB")
+				.value_hint(ValueHint::Other)
+				.requires("recursive")
+				.help_heading("RECURSIVE")
+				.action(ArgAction::Append)
+				.help("Only rename files matching GLOB"),
+		)
+		.arg(
+			Arg::new("exclude")
+				.long("exclude")
+				.value_name("GLOB")
+				.value_hint(ValueHint::Other)
+				.requires("recursive")
+				.help_heading("RECURSIVE")
+				.action(ArgAction::Append)
+				.help("Skip files and directories matching GLOB"),
+		)
+		.arg(
+			Arg::new("max-depth")
+				.long("max-depth")
+				.value_name("DEPTH")
+				.value_parser(value_parser!(usize))
+				.requires("recursive")
+				.help_heading("RECURSIVE")
+				.help("Don't go deeper than DEPTH directories"),
+		)
+		.arg(
+			Arg::new("hidden")
+				.long("hidden")
+				.requires("recursive")
+				.help_heading("RECURSIVE")
+				.action(ArgAction::SetTrue)
+				.help("Include hidden files"),
+		)
+		.arg(
+			Arg::new("gitignore")
+				.long("gitignore")
+				.requires("recursive")
+				.help_heading("RECURSIVE")
+				.action(ArgAction::SetTrue)
+				.help("Skip files ignored by .gitignore and .ignore files"),
+		)
 		.arg(
 			Arg::new("copy")
 				.long("copy")
@@ -51,6 +224,43 @@ pub fn matches() -> ArgMatches {
 				.action(ArgAction::SetTrue)
 				.help("Copy files instead of moving them"),
 		)
+		.arg(
+			Arg::new("follow-symlinks")
+				.long("follow-symlinks")
+				.short('L')
+				.help_heading("GENERAL")
+				.action(ArgAction::SetTrue)
+				.requires("copy")
+				.help("Copy what symlinks point to instead of the links"),
+		)
+		.arg(
+			Arg::new("symlinks")
+				.long("symlinks")
+				.value_name("POLICY")
+				.value_parser(EnumValueParser::<SymlinkPolicy>::new())
+				.default_value("link")
+				.help_heading("GENERAL")
+				.help("Whether symlinks or what they point to are renamed"),
+		)
+		.arg(
+			Arg::new("fix-links")
+				.long("fix-links")
+				.value_name("DIR")
+				.value_parser(value_parser!(PathBuf))
+				.value_hint(ValueHint::DirPath)
+				.action(ArgAction::Append)
+				.help_heading("GENERAL")
+				.help("Update symlinks in this directory pointing to renamed files"),
+		)
+		.arg(
+			Arg::new("link")
+				.long("link")
+				.value_name("KIND")
+				.value_parser(EnumValueParser::<LinkKind>::new())
+				.conflicts_with("copy")
+				.help_heading("GENERAL")
+				.help("Make links with the new names instead of moving files"),
+		)
 		.arg(
 			Arg::new("suffix")
 				.long("suffix")
@@ -58,6 +268,7 @@ pub fn matches() -> ArgMatches {
 				.value_name("SUFFIX")
 				.value_hint(ValueHint::Other)
 				.help_heading("SIMPLE")
+				.action(ArgAction::Append)
 				.help("Attach text to files"),
 		)
 		.arg(
@@ -113,7 +324,15 @@ pub fn matches() -> ArgMatches {
 				.value_parser(value_parser!(String))
 				.action(ArgAction::Append)
 				.value_hint(ValueHint::Other)
-				.help("Terms to search and replace"),
+				.help("Terms to search and replace (search/replace or s|search|replace|flags)"),
+		)
+		.arg(
+			Arg::new("regex")
+				.long("regex")
+				.short('E')
+				.help_heading("GENERAL")
+				.action(ArgAction::SetTrue)
+				.help("Treat search terms of --replace as regular expressions (use $1 or ${name} for groups)"),
 		)
 		.arg(
 			Arg::new("sets")
@@ -129,6 +348,14 @@ pub fn matches() -> ArgMatches {
 				.help("Ignore default set")
 				.action(ArgAction::Append),
 		)
+		.arg(
+			Arg::new("pipeline")
+				.long("pipeline")
+				.short('P')
+				.action(ArgAction::Append)
+				.value_hint(ValueHint::Other)
+				.help("Apply the steps of a pipeline from the config"),
+		)
 		.arg(
 			Arg::new("list-sets")
 				.long("list-sets")
@@ -136,19 +363,186 @@ pub fn matches() -> ArgMatches {
 				.help("List avaiable sets")
 				.action(ArgAction::SetTrue),
 		)
+		.arg(
+			Arg::new("plan")
+				.long("plan")
+				.value_name("PLAN")
+				.value_parser(value_parser!(PathBuf))
+				.value_hint(ValueHint::FilePath)
+				.help_heading("GENERAL")
+				.help("Write the planned operations to PLAN (.json or .toml) instead of running them"),
+		)
+		.arg(
+			Arg::new("output-format")
+				.long("output-format")
+				.value_name("FORMAT")
+				.value_parser(EnumValueParser::<OutputFormat>::new())
+				.value_hint(ValueHint::Other)
+				.conflicts_with_all(["plan", "swap"])
+				.help_heading("GENERAL")
+				.help("Print the planned operations (with --dry) or their results to stdout"),
+		)
+		.arg(
+			Arg::new("swap")
+				.long("swap")
+				.num_args(2)
+				.value_names(["A", "B"])
+				.value_parser(value_parser!(PathBuf))
+				.value_hint(ValueHint::AnyPath)
+				.conflicts_with("file")
+				.help_heading("GENERAL")
+				.help("Exchange the names of A and B atomically"),
+		)
+		.subcommand_negates_reqs(true)
+		.subcommand(
+			Command::new("apply")
+				.about("Run the operations of a plan written with --plan")
+				.arg(
+					Arg::new("plan")
+						.value_name("PLAN")
+						.value_parser(value_parser!(PathBuf))
+						.value_hint(ValueHint::FilePath)
+						.required(true)
+						.help("Plan to run"),
+				)
+				.arg(
+					Arg::new("dry")
+						.long("dry")
+						.short('d')
+						.action(ArgAction::SetTrue)
+						.help("Dont perfrom the operations"),
+				),
+		)
+		.subcommand(
+			Command::new("history")
+				.about("List completed batches")
+				.arg(
+					Arg::new("id")
+						.value_name("ID")
+						.value_parser(value_parser!(u64))
+						.help("Show the operations of batch ID"),
+				),
+		)
+		.subcommand(
+			Command::new("undo")
+				.about("Revert a batch, if its files haven't changed since")
+				.arg(
+					Arg::new("id")
+						.value_name("ID")
+						.value_parser(value_parser!(u64))
+						.help("Batch to revert, default: the latest one"),
+				)
+				.arg(
+					Arg::new("dry")
+						.long("dry")
+						.short('d')
+						.action(ArgAction::SetTrue)
+						.help("Dont perfrom the operations"),
+				),
+		)
+		.subcommand(
+			Command::new("recover")
+				.about("Roll back or finish runs which were interrupted")
+				.arg(
+					Arg::new("journal")
+						.value_name("JOURNAL")
+						.value_parser(value_parser!(PathBuf))
+						.value_hint(ValueHint::FilePath)
+						.help("Journal to recover, default: all interrupted runs"),
+				)
+				.arg(
+					Arg::new("rollback")
+						.long("rollback")
+						.action(ArgAction::SetTrue)
+						.conflicts_with("finish")
+						.help("Undo what was done"),
+				)
+				.arg(
+					Arg::new("finish")
+						.long("finish")
+						.action(ArgAction::SetTrue)
+						.help("Run the rest of the plan"),
+				),
+		)
 		.get_matches()
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OnConflict {
 	Overwrite,
+	#[default]
 	Skip,
 	Ask,
 }
 
-impl Default for OnConflict {
-	fn default() -> Self {
-		Self::Skip
+/// What `--symlinks` renames when a file is a symlink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+	/// The link itself, even if it's dangling.
+	#[default]
+	Link,
+	/// What the link points to.
+	Target,
+}
+
+impl clap::ValueEnum for SymlinkPolicy {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Link => PossibleValue::new("link").help("Rename the link itself"),
+			Self::Target => PossibleValue::new("target").help("Rename what the link points to"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&[Self::Link, Self::Target]
+	}
+}
+
+/// Link made by `--link`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+	Hard,
+	Symbolic,
+	/// Symbolic link with a relative path.
+	Relative,
+}
+
+impl clap::ValueEnum for LinkKind {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Hard => PossibleValue::new("hard").help("Hard link, same filesystem only"),
+			Self::Symbolic => PossibleValue::new("symbolic").help("Symlink with an absolute path"),
+			Self::Relative => PossibleValue::new("relative").help("Symlink with a relative path"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&[Self::Hard, Self::Symbolic, Self::Relative]
+	}
+}
+
+/// Format of `--output-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+	Json,
+	/// One json object per line.
+	Ndjson,
+	Tsv,
+}
+
+impl clap::ValueEnum for OutputFormat {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Json => PossibleValue::new("json").help("One json array"),
+			Self::Ndjson => PossibleValue::new("ndjson").help("One json object per line"),
+			Self::Tsv => PossibleValue::new("tsv").help("Tab separated, with a header"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&[Self::Json, Self::Ndjson, Self::Tsv]
 	}
 }
 
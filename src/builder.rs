@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+
+use crate::{
+	args::{LinkKind, OnConflict, SymlinkPolicy},
+	case::CaseConversion,
+	config::Config,
+	error::Error,
+	numbering::Numbering,
+	preserve::Preserve,
+	rename::Rename,
+	replace::Replace,
+	template::Template,
+	transform::{Step, Transform},
+	walk::Walk,
+};
+
+/// Build a [`Rename`] without going through the command line.
+#[derive(Debug, Clone, Default)]
+pub struct RenameBuilder {
+	rename: Rename,
+	steps: Vec<PendingStep>,
+	walk: Option<Walk>,
+}
+
+/// Pipelines are expanded in [`RenameBuilder::build`], once the config is known.
+#[derive(Debug, Clone)]
+enum PendingStep {
+	Step(Step),
+	Pipeline(String),
+}
+
+impl Rename {
+	pub fn builder() -> RenameBuilder {
+		RenameBuilder::default()
+	}
+}
+
+impl RenameBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	pub fn file(mut self, file: impl Into<PathBuf>) -> Self {
+		self.rename.files.push(file.into());
+		self
+	}
+	pub fn files(mut self, files: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+		self.rename.files.extend(files.into_iter().map(Into::into));
+		self
+	}
+	/// Replace directories among the files with their contents, see [`Walk::files`].
+	pub fn recursive(mut self, walk: Walk) -> Self {
+		self.walk = Some(walk);
+		self
+	}
+	/// Add a step, steps are applied in the order they are added.
+	pub fn step(mut self, step: Step) -> Self {
+		self.steps.push(PendingStep::Step(step));
+		self
+	}
+	pub fn steps(mut self, steps: impl IntoIterator<Item = Step>) -> Self {
+		self.steps.extend(steps.into_iter().map(PendingStep::Step));
+		self
+	}
+	/// Add a custom step.
+	pub fn transform(self, transform: impl Transform + 'static) -> Self {
+		self.step(Step::custom(transform))
+	}
+	pub fn prefix(self, text: impl Into<String>) -> Self {
+		self.step(Step::Prefix { text: text.into() })
+	}
+	pub fn suffix(self, text: impl Into<String>) -> Self {
+		self.step(Step::Suffix { text: text.into() })
+	}
+	pub fn replace(self, replace: Replace) -> Self {
+		self.step(Step::Replace(replace))
+	}
+	/// Apply the replacements of a set from the config.
+	pub fn set(self, name: impl Into<String>) -> Self {
+		self.step(Step::Set { name: name.into() })
+	}
+	pub fn case(self, case: CaseConversion) -> Self {
+		self.step(Step::Case(case))
+	}
+	pub fn template(self, template: Template) -> Self {
+		self.step(Step::Template { template })
+	}
+	/// Apply the steps of a pipeline from the config.
+	pub fn pipeline(mut self, name: impl Into<String>) -> Self {
+		self.steps.push(PendingStep::Pipeline(name.into()));
+		self
+	}
+	pub fn numbering(mut self, numbering: Numbering) -> Self {
+		self.rename.numbering = numbering;
+		self
+	}
+	/// Only print what would be done.
+	pub fn dry(mut self, dry: bool) -> Self {
+		self.rename.dry = dry;
+		self
+	}
+	/// Allow renaming directories.
+	pub fn dirs(mut self, dirs: bool) -> Self {
+		self.rename.dirs = dirs;
+		self
+	}
+	/// Copy files instead of renaming them.
+	pub fn copy(mut self, copy: bool) -> Self {
+		self.rename.copy = copy;
+		self
+	}
+	/// Stop at the first error.
+	pub fn fragile(mut self, fragile: bool) -> Self {
+		self.rename.fragile = fragile;
+		self
+	}
+	/// Undo all changes if an error occurs.
+	pub fn undo_on_err(mut self, undo_on_err: bool) -> Self {
+		self.rename.undo_on_err = undo_on_err;
+		self
+	}
+	/// Metadata to keep with `--copy`, moves to other filesystems keep all of it.
+	pub fn preserve(mut self, preserve: Preserve) -> Self {
+		self.rename.preserve = preserve;
+		self
+	}
+	/// Make links instead of moving or copying.
+	pub fn link(mut self, kind: LinkKind) -> Self {
+		self.rename.link = Some(kind);
+		self
+	}
+	/// Whether symlinks or what they point to are renamed.
+	pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+		self.rename.symlinks = policy;
+		self
+	}
+	/// Update symlinks in `dir` pointing to renamed files.
+	pub fn fix_links(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.rename.fix_links.push(dir.into());
+		self
+	}
+	/// Copy what symlinks point to instead of the links.
+	pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+		self.rename.follow_symlinks = follow_symlinks;
+		self
+	}
+	pub fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+		self.rename.on_conflict = on_conflict;
+		self
+	}
+	pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.rename.output_dir = Some(dir.into());
+		self
+	}
+	pub fn output_files(mut self, files: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+		self.rename.output_files = Some(files.into_iter().map(Into::into).collect());
+		self
+	}
+	/// Keep a journal in `dir` while renaming, to recover from crashes.
+	pub fn journal(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.rename.journal = Some(dir.into());
+		self
+	}
+	/// Record completed batches in the history in `dir`, so they can be undone.
+	pub fn history(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.rename.history = Some(dir.into());
+		self
+	}
+	pub fn config(mut self, config: Config) -> Self {
+		self.rename.config = config;
+		self
+	}
+	/// Check the options and expand pipelines.
+	pub fn build(self) -> Result<Rename> {
+		let Self {
+			mut rename,
+			steps,
+			walk,
+		} = self;
+		if let Some(walk) = walk {
+			rename.files = walk.files(&rename.files, rename.dirs)?;
+		}
+		for step in steps {
+			match step {
+				PendingStep::Step(step) => rename.steps.push(step),
+				PendingStep::Pipeline(name) => {
+					let steps = rename
+						.config
+						.pipelines
+						.get(&name)
+						.ok_or_else(|| Error::PipelineNotFound { name: name.clone() })?;
+					rename.steps.extend(steps.iter().cloned());
+				}
+			}
+		}
+		for step in &rename.steps {
+			match step {
+				Step::Set { name } => match rename.config.sets.get(name) {
+					Some(data) => data.set.validate()?,
+					None => Err(Error::SetNotFound { name: name.clone() })?,
+				},
+				Step::Replace(r) => r.validate()?,
+				_ => (),
+			}
+		}
+		if let Some(ref o) = rename.output_files {
+			ensure!(!o.is_empty(), "Need at least one output file");
+			let output_file_last = o.last().context("output_files is empty")?;
+			if o.len() < rename.files.len() {
+				ensure!(
+					output_file_last.exists(),
+					"Last entry of array does not exist"
+				);
+				ensure!(output_file_last.is_dir(), "Last entry isn't a directory");
+			}
+		}
+		rename.verify_output_dir()?;
+		Ok(rename)
+	}
+}
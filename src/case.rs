@@ -0,0 +1,317 @@
+use std::{ffi::OsString, fmt::Display};
+
+use anyhow::{anyhow, Result};
+use clap::builder::PossibleValue;
+use serde::{Deserialize, Serialize};
+
+/// Change the case of a name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaseConversion {
+	pub case: Case,
+	pub target: CaseTarget,
+	pub boundary: WordBoundary,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Case {
+	#[default]
+	Lower,
+	Upper,
+	/// Every word starts with a capital letter, separators are kept.
+	Title,
+	/// Only the first word starts with a capital letter, separators are kept.
+	Sentence,
+	Snake,
+	Kebab,
+	Camel,
+	Pascal,
+}
+
+/// Part of the name to convert.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseTarget {
+	#[default]
+	Stem,
+	Ext,
+	/// Stem and extension, converted separately.
+	Name,
+}
+
+/// Where words start and end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WordBoundary {
+	/// Whitespace only.
+	Space,
+	/// Whitespace, `_`, `-` and `.`.
+	#[default]
+	Separator,
+	/// Separators, changes from lower to upper case (`camelCase`) and between letters and digits.
+	All,
+}
+
+enum Token<'a> {
+	Word(&'a str),
+	Separator(&'a str),
+}
+
+impl Case {
+	pub const ALL: [Self; 8] = [
+		Self::Lower,
+		Self::Upper,
+		Self::Title,
+		Self::Sentence,
+		Self::Snake,
+		Self::Kebab,
+		Self::Camel,
+		Self::Pascal,
+	];
+	pub fn convert(&self, str: &str, boundary: WordBoundary) -> String {
+		let tokens = tokenize(str, boundary);
+		let words = || {
+			tokens.iter().filter_map(|t| match t {
+				Token::Word(w) => Some(*w),
+				Token::Separator(_) => None,
+			})
+		};
+		match self {
+			Self::Lower => str.to_lowercase(),
+			Self::Upper => str.to_uppercase(),
+			Self::Title | Self::Sentence => {
+				let mut out = String::new();
+				let mut first = true;
+				for token in &tokens {
+					match token {
+						Token::Separator(s) => out.push_str(s),
+						Token::Word(w) if first || *self == Self::Title => {
+							out.push_str(&capitalize(w));
+							first = false;
+						}
+						Token::Word(w) => out.push_str(&w.to_lowercase()),
+					}
+				}
+				out
+			}
+			Self::Snake => join(words().map(str::to_lowercase), "_"),
+			Self::Kebab => join(words().map(str::to_lowercase), "-"),
+			Self::Camel => join(
+				words().enumerate().map(|(i, w)| match i {
+					0 => w.to_lowercase(),
+					_ => capitalize(w),
+				}),
+				"",
+			),
+			Self::Pascal => join(words().map(capitalize), ""),
+		}
+	}
+}
+
+impl CaseConversion {
+	/// Convert `stem` and/or `ext` (including the leading `.`).
+	pub fn apply(&self, stem: &mut OsString, ext: &mut OsString) -> Result<()> {
+		let convert = |part: &mut OsString, prefix: &str| -> Result<()> {
+			let str = part
+				.to_str()
+				.ok_or_else(|| anyhow!("Can't change case of {part:?}, it isn't valid unicode"))?;
+			let (prefix, str) = match str.strip_prefix(prefix) {
+				Some(s) => (prefix, s),
+				None => ("", str),
+			};
+			*part = format!("{prefix}{}", self.case.convert(str, self.boundary)).into();
+			Ok(())
+		};
+		match self.target {
+			CaseTarget::Stem => convert(stem, ""),
+			CaseTarget::Ext => convert(ext, "."),
+			CaseTarget::Name => convert(stem, "").and_then(|_| convert(ext, ".")),
+		}
+	}
+}
+
+fn capitalize(word: &str) -> String {
+	let mut chars = word.chars();
+	match chars.next() {
+		Some(first) => titlecase(first) + &chars.as_str().to_lowercase(),
+		None => String::new(),
+	}
+}
+
+/// Titlecase of `c`, which only differs from the uppercase for digraphs like `ǆ`, ligatures
+/// like `ﬁ` and `ß`, and Greek letters with an iota subscript.
+fn titlecase(c: char) -> String {
+	match c {
+		'Ǆ'..='ǆ' => 'ǅ'.into(),
+		'Ǉ'..='ǉ' => 'ǈ'.into(),
+		'Ǌ'..='ǌ' => 'ǋ'.into(),
+		'Ǳ'..='ǳ' => 'ǲ'.into(),
+		'ŉ' => "ʼN".into(),
+		// Letters with iota subscript keep it instead of getting a capital iota.
+		'\u{1F80}'..='\u{1FAF}' => char::from_u32(c as u32 | 8).unwrap_or(c).into(),
+		'\u{1FB3}' | '\u{1FBC}' => '\u{1FBC}'.into(),
+		'\u{1FC3}' | '\u{1FCC}' => '\u{1FCC}'.into(),
+		'\u{1FF3}' | '\u{1FFC}' => '\u{1FFC}'.into(),
+		_ => {
+			let upper: String = c.to_uppercase().collect();
+			match upper.strip_suffix('Ι') {
+				Some(base) if !base.is_empty() && ('\u{1F00}'..='\u{1FFF}').contains(&c) => {
+					format!("{base}\u{345}")
+				}
+				// Only the first letter of ligatures is capitalized.
+				_ => {
+					let mut chars = upper.chars();
+					chars
+						.next()
+						.into_iter()
+						.chain(chars.as_str().to_lowercase().chars())
+						.collect()
+				}
+			}
+		}
+	}
+}
+
+fn join(words: impl Iterator<Item = String>, sep: &str) -> String {
+	words.collect::<Vec<_>>().join(sep)
+}
+
+fn is_separator(c: char, boundary: WordBoundary) -> bool {
+	match boundary {
+		WordBoundary::Space => c.is_whitespace(),
+		WordBoundary::Separator | WordBoundary::All => {
+			c.is_whitespace() || matches!(c, '_' | '-' | '.')
+		}
+	}
+}
+
+/// Whether a word starts at `chars[i]` without a separator before it.
+fn is_word_start(chars: &[(usize, char)], i: usize) -> bool {
+	let (Some(&(_, prev)), Some(&(_, cur))) = (chars.get(i.wrapping_sub(1)), chars.get(i)) else {
+		return false;
+	};
+	let next_lower = matches!(chars.get(i + 1), Some((_, c)) if c.is_lowercase());
+	(prev.is_lowercase() && cur.is_uppercase())
+		// Last capital of an acronym starts a new word (HTTPServer -> HTTP Server).
+		|| (prev.is_uppercase() && cur.is_uppercase() && next_lower)
+		|| (prev.is_alphabetic() && cur.is_numeric())
+		|| (prev.is_numeric() && cur.is_alphabetic())
+}
+
+fn tokenize(str: &str, boundary: WordBoundary) -> Vec<Token<'_>> {
+	let chars: Vec<(usize, char)> = str.char_indices().collect();
+	let mut out = vec![];
+	let mut start = 0;
+	let mut in_separator = matches!(chars.first(), Some(&(_, c)) if is_separator(c, boundary));
+	for i in 0..=chars.len() {
+		let (pos, separator) = match chars.get(i) {
+			Some(&(pos, c)) => (pos, is_separator(c, boundary)),
+			None => (str.len(), !in_separator),
+		};
+		let split = separator != in_separator
+			|| (boundary == WordBoundary::All && !separator && is_word_start(&chars, i));
+		if split && pos > start {
+			out.push(match in_separator {
+				true => Token::Separator(&str[start..pos]),
+				false => Token::Word(&str[start..pos]),
+			});
+			start = pos;
+		}
+		in_separator = separator;
+	}
+	out
+}
+
+impl Display for Case {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Lower => "lower",
+			Self::Upper => "UPPER",
+			Self::Title => "Title Case",
+			Self::Sentence => "Sentence case",
+			Self::Snake => "snake_case",
+			Self::Kebab => "kebab-case",
+			Self::Camel => "camelCase",
+			Self::Pascal => "PascalCase",
+		})
+	}
+}
+
+impl CaseTarget {
+	pub const ALL: [Self; 3] = [Self::Stem, Self::Ext, Self::Name];
+}
+
+impl Display for CaseTarget {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Stem => "Stem",
+			Self::Ext => "Extension",
+			Self::Name => "Whole name",
+		})
+	}
+}
+
+impl WordBoundary {
+	pub const ALL: [Self; 3] = [Self::Space, Self::Separator, Self::All];
+}
+
+impl Display for WordBoundary {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Space => "Spaces",
+			Self::Separator => "Separators",
+			Self::All => "Separators and case changes",
+		})
+	}
+}
+
+impl clap::ValueEnum for Case {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Lower => PossibleValue::new("lower").help("lower case"),
+			Self::Upper => PossibleValue::new("upper").help("UPPER CASE"),
+			Self::Title => PossibleValue::new("title").help("Title Case"),
+			Self::Sentence => PossibleValue::new("sentence").help("Sentence case"),
+			Self::Snake => PossibleValue::new("snake").help("snake_case"),
+			Self::Kebab => PossibleValue::new("kebab").help("kebab-case"),
+			Self::Camel => PossibleValue::new("camel").help("camelCase"),
+			Self::Pascal => PossibleValue::new("pascal").help("PascalCase"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&Self::ALL
+	}
+}
+
+impl clap::ValueEnum for CaseTarget {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Stem => PossibleValue::new("stem").help("Name without extension"),
+			Self::Ext => PossibleValue::new("ext").help("Extension only"),
+			Self::Name => PossibleValue::new("name").help("Stem and extension"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&Self::ALL
+	}
+}
+
+impl clap::ValueEnum for WordBoundary {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Space => PossibleValue::new("space").help("Split words at whitespace"),
+			Self::Separator => {
+				PossibleValue::new("separator").help("Split words at whitespace, '_', '-' and '.'")
+			}
+			Self::All => PossibleValue::new("all")
+				.help("Also split at case changes (camelCase) and between letters and digits"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&Self::ALL
+	}
+}
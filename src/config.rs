@@ -10,10 +10,18 @@ use serde::{Deserialize, Serialize};
 
 use error_log::{try_add, ErrorLogAnyhow};
 
+use crate::error::Error;
+use crate::replace::Replace;
+use crate::transform::Step;
+
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct Config {
 	pub default_sets: Option<Vec<String>>,
+	#[serde(default)]
 	pub sets: HashMap<String, ReplaceSetData>,
+	/// Named lists of steps.
+	#[serde(default)]
+	pub pipelines: HashMap<String, Vec<Step>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq, PartialOrd, Ord)]
@@ -36,6 +44,28 @@ pub struct ReplaceSet {
 	pub name: String,
 	pub replace: String,
 	pub search: Vec<String>,
+	/// Treat the search terms as regular expressions.
+	#[serde(default)]
+	pub regex: bool,
+}
+
+impl ReplaceSet {
+	pub fn replacements(&self) -> impl Iterator<Item = Replace> + '_ {
+		self.search.iter().map(|search| {
+			let mut replace = Replace::new(search.clone(), self.replace.clone());
+			replace.regex = self.regex;
+			replace
+		})
+	}
+	/// Check that all search terms are valid.
+	pub fn validate(&self) -> Result<()> {
+		for replace in self.replacements() {
+			replace
+				.validate()
+				.with_context(|| format!("Invalid search term in set \"{}\"", self.name))?;
+		}
+		Ok(())
+	}
 }
 
 impl Config {
@@ -50,6 +80,7 @@ impl Config {
 						description: String::from(description),
 						search,
 						replace: String::from(replace),
+						regex: false,
 					},
 					used: self.is_set_default(name),
 					editable: false,
@@ -74,7 +105,7 @@ impl Config {
 		match dir.read_dir() {
 			Ok(g) => {
 				for file in g {
-					let file = file?;
+					let file = file.map_err(|e| Error::io("read", &dir, e))?;
 					match file.path().extension() {
 						Some(ext) if ext != "toml" => {
 							warn!("{}: is not a toml", file.path().display());
@@ -86,10 +117,11 @@ impl Config {
 						}
 						_ => (),
 					};
-					let set: ReplaceSet = toml::from_str(&std::fs::read_to_string(file.path())?)
-						.with_context(|| {
-							format!("{}: Failed to parse toml", file.path().display())
-						})?;
+					let path = file.path();
+					let content =
+						std::fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+					let set: ReplaceSet = toml::from_str(&content)
+						.map_err(|source| Error::Config { path, source })?;
 					out.insert(
 						set.name.clone(),
 						ReplaceSetData {
@@ -0,0 +1,176 @@
+use std::{
+	fmt::Display,
+	io,
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Errors of the library.
+///
+/// The functions of [`crate::fs`] return it directly. Higher level functions like planning and
+/// executing return [`anyhow::Error`] with context added, the cause can be found with
+/// [`Error::find`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("File {} does not exist", path.display())]
+	NotFound { path: PathBuf },
+	/// File disappeared while being renamed.
+	#[error("{} vanished", path.display())]
+	SourceVanished { path: PathBuf },
+	/// File changed since the plan was made.
+	#[error("{} changed since the plan was made", path.display())]
+	SourceChanged { path: PathBuf },
+	#[error("{} already exists", path.display())]
+	TargetExists { path: PathBuf },
+	/// Target changed since the batch was run.
+	#[error("{} changed since it was renamed", path.display())]
+	TargetChanged { path: PathBuf },
+	/// Several files of a batch would get the same name.
+	#[error("{} files would be renamed to {}: {}", sources.len(), target.display(), display_paths(sources))]
+	Collision {
+		target: PathBuf,
+		sources: Vec<PathBuf>,
+	},
+	#[error("Permission denied: {}", path.display())]
+	PermissionDenied {
+		path: PathBuf,
+		#[source]
+		source: io::Error,
+	},
+	#[error("Failed to {action} {}", path.display())]
+	Io {
+		action: &'static str,
+		path: PathBuf,
+		#[source]
+		source: io::Error,
+	},
+	#[error("Invalid path {}", path.display())]
+	InvalidPath { path: PathBuf },
+	#[error("Output dir {} doesn't exist", path.display())]
+	OutputDirMissing { path: PathBuf },
+	#[error("Set \"{name}\" not found")]
+	SetNotFound { name: String },
+	#[error("Pipeline \"{name}\" not found")]
+	PipelineNotFound { name: String },
+	#[error("{}: Failed to parse config", path.display())]
+	Config {
+		path: PathBuf,
+		#[source]
+		source: toml::de::Error,
+	},
+}
+
+/// Kind of an [`Error`], for reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+	/// File is missing.
+	NotFound,
+	/// Target is already taken.
+	Conflict,
+	Permission,
+	Io,
+	/// Invalid options or config.
+	Config,
+	/// Not an [`Error`] of this library.
+	Other,
+}
+
+impl Error {
+	/// Sort an io error of `action` on `path` into a variant.
+	pub fn io(action: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Self {
+		let path = path.into();
+		match source.kind() {
+			io::ErrorKind::NotFound => Self::NotFound { path },
+			io::ErrorKind::AlreadyExists => Self::TargetExists { path },
+			io::ErrorKind::PermissionDenied => Self::PermissionDenied { path, source },
+			_ => Self::Io {
+				action,
+				path,
+				source,
+			},
+		}
+	}
+	/// Sort an io error of `action` from `from` to `to` into a variant, with the path which
+	/// caused it: `to` if it exists already, the directory of `to` if it's missing.
+	pub fn io_to(action: &'static str, from: &Path, to: &Path, source: io::Error) -> Self {
+		match source.kind() {
+			io::ErrorKind::AlreadyExists => Self::TargetExists { path: to.into() },
+			io::ErrorKind::NotFound if from.symlink_metadata().is_ok() => Self::NotFound {
+				path: to.parent().unwrap_or(to).into(),
+			},
+			_ => Self::io_source(action, from, source),
+		}
+	}
+	/// Like [`Self::io`] for the source of an operation, which vanished if it's missing.
+	pub fn io_source(action: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Self {
+		match source.kind() {
+			io::ErrorKind::NotFound => Self::SourceVanished { path: path.into() },
+			_ => Self::io(action, path, source),
+		}
+	}
+	/// File the error is about.
+	pub fn path(&self) -> Option<&PathBuf> {
+		match self {
+			Self::NotFound { path }
+			| Self::SourceVanished { path }
+			| Self::SourceChanged { path }
+			| Self::TargetExists { path }
+			| Self::TargetChanged { path }
+			| Self::Collision { target: path, .. }
+			| Self::PermissionDenied { path, .. }
+			| Self::Io { path, .. }
+			| Self::InvalidPath { path }
+			| Self::OutputDirMissing { path }
+			| Self::Config { path, .. } => Some(path),
+			Self::SetNotFound { .. } | Self::PipelineNotFound { .. } => None,
+		}
+	}
+	pub fn category(&self) -> ErrorCategory {
+		match self {
+			Self::NotFound { .. } | Self::SourceVanished { .. } => ErrorCategory::NotFound,
+			Self::TargetExists { .. }
+			| Self::TargetChanged { .. }
+			| Self::SourceChanged { .. }
+			| Self::Collision { .. } => ErrorCategory::Conflict,
+			Self::PermissionDenied { .. } => ErrorCategory::Permission,
+			Self::Io { .. } | Self::InvalidPath { .. } => ErrorCategory::Io,
+			Self::OutputDirMissing { .. }
+			| Self::SetNotFound { .. }
+			| Self::PipelineNotFound { .. }
+			| Self::Config { .. } => ErrorCategory::Config,
+		}
+	}
+	/// First [`Error`] in the chain of `err`.
+	pub fn find(err: &anyhow::Error) -> Option<&Self> {
+		err.chain().find_map(|e| e.downcast_ref::<Self>())
+	}
+}
+
+pub(crate) fn display_paths(paths: &[PathBuf]) -> String {
+	paths
+		.iter()
+		.map(|p| p.display().to_string())
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+impl ErrorCategory {
+	pub fn of(err: &anyhow::Error) -> Self {
+		Error::find(err).map_or(Self::Other, Error::category)
+	}
+}
+
+impl Display for ErrorCategory {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::NotFound => "not found",
+			Self::Conflict => "conflict",
+			Self::Permission => "permission",
+			Self::Io => "io",
+			Self::Config => "config",
+			Self::Other => "other",
+		})
+	}
+}
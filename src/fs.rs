@@ -0,0 +1,404 @@
+use std::{
+	collections::HashSet,
+	fs::{File, OpenOptions},
+	io::{self, Read, Write},
+	path::{Component, Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::{
+	args::LinkKind,
+	error::Error,
+	order::temp_name,
+	preserve::{Attribute, Preserve},
+};
+
+/// Move `from` to `to`, failing with [`Error::TargetExists`] instead of replacing `to`.
+///
+/// Uses `renameat2(RENAME_NOREPLACE)` where possible (Linux with glibc) and falls back to link
+/// and unlink. Where neither works, like for directories elsewhere or on filesystems without hard
+/// links, `to` is checked before a plain rename. That isn't atomic, a target which appears in between can be
+/// replaced, so a warning is logged.
+pub fn rename_noreplace(from: &Path, to: &Path) -> Result<(), Error> {
+	noreplace(from, to).map_err(|e| Error::io_to("rename", from, to, e))
+}
+
+fn noreplace(from: &Path, to: &Path) -> io::Result<()> {
+	#[cfg(all(target_os = "linux", target_env = "gnu"))]
+	{
+		use nix::{
+			errno::Errno,
+			fcntl::{renameat2, RenameFlags},
+		};
+		match renameat2(None, from, None, to, RenameFlags::RENAME_NOREPLACE) {
+			Ok(()) => return Ok(()),
+			// Not supported by the kernel or filesystem.
+			Err(Errno::EINVAL | Errno::ENOSYS | Errno::EOPNOTSUPP) => (),
+			Err(e) => return Err(e.into()),
+		}
+	}
+	#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+	no_renameat2();
+	link_unlink(from, to)
+}
+
+/// `renameat2` is only wrapped by nix for glibc, other Linux builds like musl use the fallbacks.
+#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+fn no_renameat2() {
+	static WARNED: std::sync::Once = std::sync::Once::new();
+	WARNED.call_once(|| {
+		warn!("Built without renameat2, which needs glibc: swaps and moves of directories aren't atomic")
+	});
+}
+
+fn link_unlink(from: &Path, to: &Path) -> io::Result<()> {
+	match std::fs::hard_link(from, to) {
+		// Don't leave the file behind twice.
+		Ok(()) => std::fs::remove_file(from).inspect_err(|_| {
+			let _ = std::fs::remove_file(to);
+		}),
+		Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+		// Other filesystem, moved by copying without a rename.
+		Err(e) if e.kind() == io::ErrorKind::CrossesDevices => Err(e),
+		// Directories and filesystems without hard links can't be moved atomically. Directories
+		// on other filesystems fail here too and are copied, without a warning.
+		Err(_) => match to.symlink_metadata() {
+			Ok(_) => Err(io::ErrorKind::AlreadyExists.into()),
+			Err(_) => std::fs::rename(from, to).inspect(|_| {
+				warn!(
+					"Moved {} without an atomic check for {}, it was replaced if it appeared meanwhile",
+					from.display(),
+					to.display()
+				)
+			}),
+		},
+	}
+}
+
+/// Exchange `a` and `b`.
+///
+/// Uses `renameat2(RENAME_EXCHANGE)` where possible (Linux with glibc), which is atomic. Else `a`
+/// is moved to a temporary name, `b` to `a` and the temporary name to `b`. Meanwhile `b` doesn't
+/// exist, and if the process dies `a`'s contents can be left under the temporary name. Failed
+/// steps are rolled back. [`Rename::swap`](crate::Rename::swap) journals the swap as a whole.
+pub fn swap(a: &Path, b: &Path) -> Result<(), Error> {
+	exchange(a, b).map_err(|e| match e.kind() {
+		io::ErrorKind::NotFound if a.symlink_metadata().is_ok() => Error::io_source("swap", b, e),
+		_ => Error::io_source("swap", a, e),
+	})
+}
+
+fn exchange(a: &Path, b: &Path) -> io::Result<()> {
+	#[cfg(all(target_os = "linux", target_env = "gnu"))]
+	{
+		use nix::{
+			errno::Errno,
+			fcntl::{renameat2, RenameFlags},
+		};
+		match renameat2(None, a, None, b, RenameFlags::RENAME_EXCHANGE) {
+			Ok(()) => return Ok(()),
+			// Not supported by the kernel or filesystem.
+			Err(Errno::EINVAL | Errno::ENOSYS | Errno::EOPNOTSUPP) => (),
+			Err(e) => return Err(e.into()),
+		}
+	}
+	#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+	no_renameat2();
+	let temp = temp_name(a, &HashSet::new());
+	noreplace(a, &temp)?;
+	if let Err(e) = noreplace(b, a) {
+		let _ = noreplace(&temp, a);
+		return Err(e);
+	}
+	if let Err(e) = noreplace(&temp, b) {
+		// Put everything back.
+		let _ = noreplace(a, b).and_then(|_| noreplace(&temp, a));
+		return Err(e);
+	}
+	Ok(())
+}
+
+type Progress = Box<dyn FnMut(&Path, u64, u64)>;
+type Lost = Box<dyn FnMut(&Path, Attribute, io::Error)>;
+
+/// How files are copied by [`move_across`] and [`copy_with`].
+pub struct CopyOptions {
+	pub preserve: Preserve,
+	/// Copy what symlinks point to instead of the links.
+	pub follow_symlinks: bool,
+	progress: Progress,
+	lost: Lost,
+}
+
+impl Default for CopyOptions {
+	fn default() -> Self {
+		Self::new(Preserve::default())
+	}
+}
+
+impl CopyOptions {
+	pub fn new(preserve: Preserve) -> Self {
+		Self {
+			preserve,
+			follow_symlinks: false,
+			progress: Box::new(|_, _, _| ()),
+			lost: Box::new(|_, _, _| ()),
+		}
+	}
+	pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+		self.follow_symlinks = follow_symlinks;
+		self
+	}
+	/// Called with the file, the bytes copied and its size while copying.
+	pub fn progress(mut self, progress: impl FnMut(&Path, u64, u64) + 'static) -> Self {
+		self.progress = Box::new(progress);
+		self
+	}
+	/// Called with the copy for every attribute which couldn't be kept.
+	pub fn lost(mut self, lost: impl FnMut(&Path, Attribute, io::Error) + 'static) -> Self {
+		self.lost = Box::new(lost);
+		self
+	}
+	fn keep(&mut self, from: &Path, to: &Path) {
+		for (attribute, e) in self.preserve.apply(from, to) {
+			(self.lost)(to, attribute, e)
+		}
+	}
+}
+
+/// Like [`rename_noreplace`], but moves to other filesystems with [`move_across`].
+pub fn move_noreplace(from: &Path, to: &Path, opts: &mut CopyOptions) -> Result<(), Error> {
+	match noreplace(from, to) {
+		Err(e) if e.kind() == io::ErrorKind::CrossesDevices => move_across(from, to, opts),
+		res => res.map_err(|e| Error::io_to("rename", from, to, e)),
+	}
+}
+
+/// Move the file or directory tree `from` to another filesystem.
+///
+/// Everything is copied with [`copy_with`] before `from` is removed, symlinks are always moved
+/// as they are.
+pub fn move_across(from: &Path, to: &Path, opts: &mut CopyOptions) -> Result<(), Error> {
+	let follow_symlinks = std::mem::replace(&mut opts.follow_symlinks, false);
+	let res = copy_with(from, to, opts);
+	opts.follow_symlinks = follow_symlinks;
+	res?;
+	let is_dir = from
+		.symlink_metadata()
+		.map_err(|e| Error::io_source("read metadata of", from, e))?
+		.is_dir();
+	remove_path(from).inspect_err(|_| {
+		// Source is still complete, don't keep it twice.
+		if !is_dir {
+			let _ = remove_path(to);
+		}
+	})
+}
+
+/// Copy the file or directory tree `from` to `to`, failing with [`Error::TargetExists`]
+/// instead of replacing `to`.
+///
+/// Everything is copied to a temporary name next to `to`, synced to disk and checked before it
+/// gets its name. The copy is removed again if anything fails.
+pub fn copy_with(from: &Path, to: &Path, opts: &mut CopyOptions) -> Result<(), Error> {
+	if to.symlink_metadata().is_ok() {
+		return Err(Error::TargetExists { path: to.into() });
+	}
+	let temp = temp_name(to, &HashSet::new());
+	if let Err(e) = copy_tree(from, &temp, opts, &mut vec![])
+		.map_err(|e| Error::io_to("copy", from, to, e))
+		.and_then(|_| rename_noreplace(&temp, to))
+	{
+		let _ = remove_path(&temp);
+		return Err(e);
+	}
+	if let Some(parent) = to.parent() {
+		// Make the new name durable, not possible everywhere.
+		let _ = File::open(parent).and_then(|d| d.sync_all());
+	}
+	Ok(())
+}
+
+/// `ancestors` are the directories being copied, only kept when following symlinks.
+fn copy_tree(
+	from: &Path,
+	to: &Path,
+	opts: &mut CopyOptions,
+	ancestors: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+	let mut meta = from.symlink_metadata()?;
+	let from = match opts.follow_symlinks && meta.is_symlink() {
+		true => {
+			let real = from.canonicalize()?;
+			meta = real.symlink_metadata()?;
+			real
+		}
+		false => from.to_owned(),
+	};
+	if meta.is_dir() {
+		if opts.follow_symlinks {
+			let real = from.canonicalize()?;
+			if ancestors.contains(&real) {
+				return Err(io::Error::other(format!(
+					"Symlink loop at {}",
+					from.display()
+				)));
+			}
+			ancestors.push(real);
+		}
+		std::fs::create_dir(to)?;
+		for entry in std::fs::read_dir(&from)? {
+			let entry = entry?;
+			copy_tree(&entry.path(), &to.join(entry.file_name()), opts, ancestors)?;
+		}
+		std::fs::set_permissions(to, meta.permissions())?;
+		if opts.follow_symlinks {
+			ancestors.pop();
+		}
+	} else if meta.is_symlink() {
+		copy_symlink(&from, to)?;
+	} else {
+		copy_verified(&from, to, &mut opts.progress)?;
+	}
+	opts.keep(&from, to);
+	Ok(())
+}
+
+/// Copy a file and check the size of the copy once it's on disk.
+fn copy_verified(
+	from: &Path,
+	to: &Path,
+	progress: &mut dyn FnMut(&Path, u64, u64),
+) -> io::Result<()> {
+	let mut source = File::open(from)?;
+	let meta = source.metadata()?;
+	let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+	let mut buf = vec![0; 1 << 20];
+	let mut copied: u64 = 0;
+	loop {
+		let n = match source.read(&mut buf) {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		};
+		target.write_all(&buf[..n])?;
+		copied += n as u64;
+		progress(from, copied, meta.len());
+	}
+	target.set_permissions(meta.permissions())?;
+	target.sync_all()?;
+	if copied != meta.len() || target.metadata()?.len() != meta.len() {
+		return Err(io::Error::other(format!(
+			"Size of the copy of {} doesn't match",
+			from.display()
+		)));
+	}
+	Ok(())
+}
+
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+	symlink(&std::fs::read_link(from)?, to, from.is_dir())
+}
+
+/// Make `to` a link to `from`, failing with [`Error::TargetExists`] instead of replacing `to`.
+///
+/// Symbolic links point to the absolute path of `from`, relative ones to its path from the
+/// directory of `to`. Symlinks in the parent directories are resolved for both.
+pub fn link(from: &Path, to: &Path, kind: LinkKind) -> Result<(), Error> {
+	make_link(from, to, kind).map_err(|e| Error::io_to("link", from, to, e))
+}
+
+fn make_link(from: &Path, to: &Path, kind: LinkKind) -> io::Result<()> {
+	match kind {
+		LinkKind::Hard => std::fs::hard_link(from, to),
+		LinkKind::Symbolic => symlink(&real_parent(from)?, to, from.is_dir()),
+		LinkKind::Relative => {
+			let link = real_parent(to)?;
+			let dir = link.parent().unwrap_or(Path::new("/"));
+			symlink(&relative_path(dir, &real_parent(from)?), to, from.is_dir())
+		}
+	}
+}
+
+/// `path` with its parent directory resolved, the last component is kept even if it's a
+/// symlink.
+fn real_parent(path: &Path) -> io::Result<PathBuf> {
+	let name = path.file_name().ok_or_else(|| {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("{} has no file name", path.display()),
+		)
+	})?;
+	let parent = match path.parent() {
+		Some(p) if p != Path::new("") => p,
+		_ => Path::new("."),
+	};
+	Ok(parent.canonicalize()?.join(name))
+}
+
+/// Path of `to` from the directory `dir`, both absolute. `to` itself if they have nothing in
+/// common, like different drives.
+pub(crate) fn relative_path(dir: &Path, to: &Path) -> PathBuf {
+	let dir: Vec<Component> = dir.components().collect();
+	let to: Vec<Component> = to.components().collect();
+	let common = dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+	if common == 0 {
+		return to.into_iter().collect();
+	}
+	dir[common..]
+		.iter()
+		.map(|_| Component::ParentDir)
+		.chain(to[common..].iter().copied())
+		.collect()
+}
+
+#[cfg(unix)]
+pub(crate) fn symlink(original: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+	std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+pub(crate) fn symlink(original: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+	match is_dir {
+		true => std::os::windows::fs::symlink_dir(original, link),
+		false => std::os::windows::fs::symlink_file(original, link),
+	}
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn symlink(_: &Path, link: &Path, _: bool) -> io::Result<()> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		format!("Can't create symlink {}", link.display()),
+	))
+}
+
+/// Remove the file or directory `path`.
+pub fn remove_path(path: &Path) -> Result<(), Error> {
+	match path.symlink_metadata() {
+		Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(path),
+		Ok(_) => std::fs::remove_file(path),
+		Err(e) => Err(e),
+	}
+	.map_err(|e| Error::io("remove", path, e))
+}
+
+/// Copy the file `from` to `to`, failing with [`Error::TargetExists`] instead of replacing `to`.
+pub fn copy_noreplace(from: &Path, to: &Path) -> Result<u64, Error> {
+	copy_file(from, to).map_err(|e| Error::io_to("copy", from, to, e))
+}
+
+fn copy_file(from: &Path, to: &Path) -> io::Result<u64> {
+	let mut source = File::open(from)?;
+	let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+	let copied = io::copy(&mut source, &mut target).and_then(|n| {
+		target.set_permissions(source.metadata()?.permissions())?;
+		Ok(n)
+	});
+	if copied.is_err() {
+		let _ = std::fs::remove_file(to);
+	}
+	copied
+}
@@ -1,3 +1,12 @@
+use std::{
+	ffi::{OsStr, OsString},
+	path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::raw_path;
+
 pub fn into_none_if<T>(cond: bool, obj: T) -> Option<T> {
 	if cond {
 		None
@@ -5,3 +14,42 @@ pub fn into_none_if<T>(cond: bool, obj: T) -> Option<T> {
 		Some(obj)
 	}
 }
+
+/// Run `f` on the raw bytes of `str`.
+/// Names don't need to be valid UTF-8 on unix.
+pub fn map_os_bytes(str: &OsStr, f: impl FnOnce(&[u8]) -> Vec<u8>) -> Result<OsString> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::ffi::{OsStrExt, OsStringExt};
+		Ok(OsString::from_vec(f(str.as_bytes())))
+	}
+	#[cfg(not(unix))]
+	{
+		use anyhow::Context;
+		let str = str
+			.to_str()
+			.context("Names which aren't valid unicode are only supported on unix")?;
+		Ok(String::from_utf8(f(str.as_bytes()))
+			.context("Result isn't valid unicode")?
+			.into())
+	}
+}
+
+/// Paths in `input`, one per line or separated by NUL bytes with `null`. Empty ones are skipped.
+/// The bytes are kept as they are on unix, so names don't need to be valid UTF-8.
+pub fn parse_paths(input: &[u8], null: bool) -> Result<Vec<PathBuf>> {
+	let separator = match null {
+		true => b'\0',
+		false => b'\n',
+	};
+	input
+		.split(|b| *b == separator)
+		// Lists written on windows end their lines with CRLF.
+		.map(|path| match cfg!(unix) || null {
+			true => path,
+			false => path.strip_suffix(b"\r").unwrap_or(path),
+		})
+		.filter(|path| !path.is_empty())
+		.map(|path| raw_path::from_bytes(path.to_vec()).map_err(|e| anyhow!(e)))
+		.collect()
+}
@@ -0,0 +1,296 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use error_log::{return_ok, try_add, ErrorLogAnyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	args::OnConflict,
+	error::Error,
+	fs::{move_noreplace, remove_path, swap},
+	plan::{copy_options, remove_created, Fingerprint, Operation, OperationKind, Outcome},
+	preserve::Preserve,
+	relink::Relinked,
+};
+
+/// Completed batches, one JSON file per batch.
+#[derive(Debug, Clone)]
+pub struct History {
+	dir: PathBuf,
+}
+
+/// Batch of operations done by one run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+	pub id: u64,
+	pub time: SystemTime,
+	/// Command line of the process which ran the batch.
+	#[serde(default)]
+	pub command: Vec<String>,
+	#[serde(default)]
+	pub on_conflict: OnConflict,
+	pub operations: Vec<Done>,
+	/// Symlinks which were updated to point to the new names.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub relinked: Vec<Relinked>,
+	/// When the batch was undone.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub undone: Option<SystemTime>,
+}
+
+/// Operation of a [`Batch`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Done {
+	pub kind: OperationKind,
+	#[serde(with = "crate::raw_path")]
+	pub source: PathBuf,
+	#[serde(with = "crate::raw_path")]
+	pub target: PathBuf,
+	/// State of the target after the batch, `None` if it was moved on by the batch (like the
+	/// temporary name of a cycle) or merged.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub fingerprint: Option<Fingerprint>,
+	/// What a merge created inside the target, only this is removed on undo.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "crate::raw_path::option_vec"
+	)]
+	pub created: Option<Vec<PathBuf>>,
+	/// Reverted by an undo which failed for other operations, skipped when undoing again.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub undone: bool,
+}
+
+impl History {
+	/// Where the command line keeps its history.
+	pub fn default_dir() -> Option<PathBuf> {
+		dirs::state_dir()
+			.or_else(dirs::data_local_dir)
+			.map(|d| d.join("renameplus").join("history"))
+	}
+	pub fn new(dir: impl Into<PathBuf>) -> Self {
+		Self { dir: dir.into() }
+	}
+	/// Save the operations which were done as a new batch.
+	///
+	/// Paths are made absolute, so the batch can be undone from anywhere.
+	pub fn record(
+		&self,
+		on_conflict: OnConflict,
+		done: &[(&Operation, &Outcome)],
+		relinked: Vec<Relinked>,
+	) -> Result<u64> {
+		let cwd = std::env::current_dir().context("Failed to get current dir")?;
+		let operations = done
+			.iter()
+			.enumerate()
+			.map(|(i, (op, outcome))| {
+				let target = &outcome.target;
+				let moved_on = done[i + 1..]
+					.iter()
+					.any(|(later, _)| later.source == *target);
+				Ok(Done {
+					kind: op.kind,
+					source: cwd.join(&op.source),
+					target: cwd.join(target),
+					fingerprint: match moved_on || outcome.created.is_some() {
+						true => None,
+						false => Some(Fingerprint::of(target)?),
+					},
+					created: outcome
+						.created
+						.as_ref()
+						.map(|c| c.iter().map(|p| cwd.join(p)).collect()),
+					undone: false,
+				})
+			})
+			.collect::<Result<Vec<Done>, Error>>()?;
+		fs::create_dir_all(&self.dir).map_err(|e| Error::io("create", &self.dir, e))?;
+		let mut id = self.batches()?.last().map_or(1, |b| b.id + 1);
+		let mut batch = Batch {
+			id,
+			time: SystemTime::now(),
+			command: std::env::args_os()
+				.map(|a| a.to_string_lossy().into_owned())
+				.collect(),
+			on_conflict,
+			operations,
+			relinked,
+			undone: None,
+		};
+		// Another run may take the same id.
+		loop {
+			let path = self.path(id);
+			match fs::OpenOptions::new()
+				.write(true)
+				.create_new(true)
+				.open(&path)
+			{
+				Ok(file) => {
+					serde_json::to_writer_pretty(file, &batch)
+						.with_context(|| format!("Failed to write {}", path.display()))?;
+					return Ok(id);
+				}
+				Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+					id += 1;
+					batch.id = id;
+				}
+				Err(e) => Err(Error::io("create", &path, e))?,
+			}
+		}
+	}
+	/// All batches, oldest first. Files which can't be read are skipped with a warning.
+	pub fn batches(&self) -> Result<Vec<Batch>> {
+		let entries = match fs::read_dir(&self.dir) {
+			Ok(entries) => entries,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(e) => Err(Error::io("read", &self.dir, e))?,
+		};
+		let mut out = vec![];
+		for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+			if matches!(path.extension(), Some(e) if e == "json") {
+				match Self::load(&path) {
+					Ok(batch) => out.push(batch),
+					Err(e) => warn!("Skipping {}: {e:#}", path.display()),
+				}
+			}
+		}
+		out.sort_by_key(|b| b.id);
+		Ok(out)
+	}
+	pub fn get(&self, id: u64) -> Result<Batch> {
+		Self::load(&self.path(id)).with_context(|| format!("No batch with id {id}"))
+	}
+	/// Latest batch which wasn't undone.
+	pub fn last(&self) -> Result<Option<Batch>> {
+		Ok(self
+			.batches()?
+			.into_iter()
+			.rev()
+			.find(|b| b.undone.is_none()))
+	}
+	/// Revert the batch `id`, or the latest one.
+	pub fn undo(&self, id: Option<u64>, dry: bool) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		let mut batch = match id {
+			Some(id) => try_add!(self.get(id), err),
+			None => match try_add!(self.last(), err) {
+				Some(batch) => batch,
+				None => {
+					err += anyhow::anyhow!("Nothing to undo");
+					return err;
+				}
+			},
+		};
+		if batch.undone.is_some() {
+			err += anyhow::anyhow!("Batch {} was already undone", batch.id);
+			return err;
+		}
+		err.append_entries(&mut batch.undo(dry));
+		if !dry {
+			// Also after a partial undo, so the reverted operations are skipped next time.
+			if err.entries().is_empty() {
+				batch.undone = Some(SystemTime::now());
+			}
+			try_add!(self.save(&batch), err);
+		}
+		return_ok!((), err);
+	}
+	fn save(&self, batch: &Batch) -> Result<()> {
+		let path = self.path(batch.id);
+		let json = serde_json::to_string_pretty(batch).context("Failed to serialize batch")?;
+		fs::write(&path, json).map_err(|e| Error::io("write", &path, e))?;
+		Ok(())
+	}
+	fn load(path: &Path) -> Result<Batch> {
+		let json = fs::read_to_string(path).map_err(|e| Error::io("read", path, e))?;
+		serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+	}
+	fn path(&self, id: u64) -> PathBuf {
+		self.dir.join(format!("{id}.json"))
+	}
+}
+
+impl Batch {
+	/// Check that every target is still as it was left by the batch.
+	pub fn verify(&self) -> Result<()> {
+		for done in self.operations.iter().filter(|d| !d.undone) {
+			let Some(fingerprint) = done.fingerprint else {
+				continue;
+			};
+			if done.target.symlink_metadata().is_err() {
+				Err(Error::NotFound {
+					path: done.target.clone(),
+				})?
+			}
+			if Fingerprint::of(&done.target)? != fingerprint {
+				Err(Error::TargetChanged {
+					path: done.target.clone(),
+				})?
+			}
+		}
+		Ok(())
+	}
+	/// Some operations were reverted by an undo which failed for others.
+	pub fn partially_undone(&self) -> bool {
+		self.undone.is_none()
+			&& (self.operations.iter().any(|d| d.undone) || self.relinked.iter().any(|r| r.undone))
+	}
+	/// Revert the operations, newest first. Nothing is touched if a target changed.
+	///
+	/// What was reverted is marked as [`Done::undone`], so it's skipped if the batch is undone
+	/// again after a failure.
+	pub fn undo(&mut self, dry: bool) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		try_add!(
+			self.verify()
+				.with_context(|| format!("Can't undo batch {}", self.id)),
+			err
+		);
+		for relinked in self.relinked.iter_mut().rev().filter(|r| !r.undone) {
+			let link = &relinked.link;
+			info!(
+				"{}: {} -> {}",
+				link.display(),
+				relinked.new.display(),
+				relinked.old.display()
+			);
+			if !dry {
+				match relinked.undo() {
+					Ok(()) => relinked.undone = true,
+					Err(e) => err += e.context(format!("Failed to restore {}", link.display())),
+				}
+			}
+		}
+		for done in self.operations.iter_mut().rev().filter(|d| !d.undone) {
+			info!("{} -> {}", done.target.display(), done.source.display());
+			if dry {
+				info!("{} Skipped because of \'--dry\'.", done.target.display());
+				continue;
+			}
+			let res = match (done.kind, &done.created) {
+				(OperationKind::Copy, Some(created)) => remove_created(created),
+				(OperationKind::Copy, None) | (OperationKind::Link(_), _) => {
+					remove_path(&done.target)
+				}
+				(OperationKind::Swap, _) => swap(&done.target, &done.source),
+				_ => move_noreplace(
+					&done.target,
+					&done.source,
+					&mut copy_options(Preserve::all()),
+				),
+			};
+			match res {
+				Ok(()) => done.undone = true,
+				Err(e) => err += anyhow::Error::from(e),
+			}
+		}
+		return_ok!((), err);
+	}
+}
@@ -4,8 +4,11 @@ use log::{debug, error, info};
 use snake_helper::unwrap_or_print_err;
 
 use crate::args::OnConflict;
+use crate::journal::{Recovery, Unfinished};
+use crate::plan::{OperationKind, Reason, RenamePlan};
 use crate::rename::Rename;
 
+use std::collections::HashSet;
 use std::fs::rename;
 use std::path::PathBuf;
 
@@ -36,7 +39,88 @@ pub fn read_input_loop(limit: u8) -> Result<String> {
 	res
 }
 
+/// Ask what to do with the interrupted run of `journal`.
+pub fn recover_ask(journal: &Unfinished) -> Result<Recovery> {
+	println!(
+		"{}: interrupted after {} of {} operations",
+		journal.path.display(),
+		journal.started.len(),
+		journal.plan.operations.len()
+	);
+	for (_, op) in &journal.started {
+		if let Some(ref target) = op.target {
+			println!("  {} -> {}", op.source.display(), target.display());
+		}
+	}
+	loop {
+		print!("[r]oll back, [f]inish, [k]eep: ");
+		let input: String = unwrap_or_print_err!(read_input_loop(5), continue)
+			.trim()
+			.into();
+		match input.as_str() {
+			"r" => return Ok(Recovery::Rollback),
+			"f" => return Ok(Recovery::Finish),
+			"k" => return Ok(Recovery::Keep),
+			i => error!("Invalid input: {:?}", i),
+		}
+	}
+}
+
 impl Rename {
+	/// Ask what to do with the conflicts of `plan`.
+	pub fn resolve_conflicts(&self, plan: &mut RenamePlan) -> Result<()> {
+		// Targets of other files of the batch.
+		let mut taken: HashSet<PathBuf> = plan
+			.operations
+			.iter()
+			.filter(|op| {
+				matches!(
+					op.kind,
+					OperationKind::Move | OperationKind::Copy | OperationKind::Link(_)
+				)
+			})
+			.filter_map(|op| op.target.clone())
+			.collect();
+		// Targets of colliding files, nobody gets them without being asked.
+		let shared: HashSet<PathBuf> = plan
+			.operations
+			.iter()
+			.filter(|op| matches!(op.reason, Some(Reason::Collision(_))))
+			.filter_map(|op| op.target.clone())
+			.collect();
+		for op in &mut plan.operations {
+			if op.kind != OperationKind::Conflict {
+				continue;
+			}
+			let mut target = op.target.clone().context("Conflict without target")?;
+			(op.kind, op.reason) = loop {
+				let exists = target.symlink_metadata().is_ok();
+				if !exists && !taken.contains(&target) && !shared.contains(&target) {
+					break (self.operation_kind(), None);
+				}
+				match self.conflict_ask(&target)? {
+					(Some(p), _) => target = p,
+					// Files of one run never overwrite each other.
+					(None, OnConflict::Overwrite) if taken.contains(&target) => error!(
+						"{} is the new name of another file, choose another one or skip",
+						target.display()
+					),
+					(None, OnConflict::Overwrite) => {
+						break (self.operation_kind(), exists.then_some(Reason::Overwrite))
+					}
+					(None, OnConflict::Skip) => {
+						break (OperationKind::Skip, Some(Reason::TargetExists))
+					}
+					(None, OnConflict::Ask) => (),
+				}
+			};
+			if op.kind != OperationKind::Skip {
+				taken.insert(target.clone());
+			}
+			op.target = Some(target);
+		}
+		Ok(())
+	}
 	pub fn conflict_ask(&self, new_path: &PathBuf) -> Result<(Option<PathBuf>, OnConflict)> {
 		loop {
 			print!(
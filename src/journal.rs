@@ -0,0 +1,426 @@
+use std::{
+	collections::{HashMap, HashSet},
+	fs::{self, File, OpenOptions, TryLockError},
+	io::{BufRead, BufReader, Write},
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use error_log::{return_ok, ErrorLogAnyhow};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	error::Error,
+	fs::{move_noreplace, remove_path, rename_noreplace, swap},
+	plan::{
+		copy_options, remove_created, Fingerprint, Operation, OperationKind, Outcome, Reason,
+		RenamePlan,
+	},
+	preserve::Preserve,
+};
+
+/// Record of a running [`RenamePlan`], written to disk before and after every operation.
+///
+/// The file is removed once the plan is done, if it's still there on the next start the run
+/// was interrupted and can be rolled back or finished with [`Unfinished`]. It's locked while the
+/// run is going, so other runs leave it alone.
+#[derive(Debug)]
+pub struct Journal {
+	path: PathBuf,
+	file: File,
+}
+
+/// Line of a journal file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Entry {
+	Begin {
+		pid: u32,
+		plan: RenamePlan,
+	},
+	/// Operation is about to run, with the target it's really run with.
+	Started {
+		index: usize,
+		operation: Operation,
+	},
+	/// Target of the operation is about to be moved to `stash` to be overwritten.
+	Stashed {
+		index: usize,
+		/// Entry inside the target of a merge, the target itself if `None`.
+		#[serde(
+			default,
+			skip_serializing_if = "Option::is_none",
+			with = "crate::raw_path::option"
+		)]
+		target: Option<PathBuf>,
+		#[serde(with = "crate::raw_path")]
+		stash: PathBuf,
+	},
+	/// `path` is about to be copied into the target of a merge.
+	Created {
+		index: usize,
+		#[serde(with = "crate::raw_path")]
+		path: PathBuf,
+	},
+	Completed {
+		index: usize,
+	},
+	Undone {
+		index: usize,
+	},
+}
+
+impl Journal {
+	/// Where the command line keeps its journals.
+	pub fn default_dir() -> Option<PathBuf> {
+		dirs::data_local_dir().map(|d| d.join("renameplus").join("journal"))
+	}
+	/// Start a journal for `plan` in `dir`.
+	pub fn create(dir: &Path, plan: &RenamePlan) -> Result<Self> {
+		fs::create_dir_all(dir).map_err(|e| Error::io("create", dir, e))?;
+		let pid = std::process::id();
+		let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.f");
+		let path = dir.join(format!("{stamp}-{pid}.ndjson"));
+		let file = OpenOptions::new()
+			.append(true)
+			.create_new(true)
+			.open(&path)
+			.map_err(|e| Error::io("create", &path, e))?;
+		// Released when the file is closed, also if the process dies.
+		if let Err(e) = file.try_lock() {
+			warn!(
+				"Failed to lock {}, other runs may take it as interrupted: {e}",
+				path.display()
+			)
+		}
+		let mut out = Self { path, file };
+		out.write(&Entry::Begin {
+			pid,
+			plan: RenamePlan {
+				journal: None,
+				history: None,
+				..plan.clone()
+			},
+		})?;
+		Ok(out)
+	}
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+	pub(crate) fn started(&mut self, index: usize, operation: &Operation) -> Result<()> {
+		self.write(&Entry::Started {
+			index,
+			operation: operation.clone(),
+		})
+	}
+	pub(crate) fn stashed(
+		&mut self,
+		index: usize,
+		target: Option<&Path>,
+		stash: &Path,
+	) -> Result<()> {
+		self.write(&Entry::Stashed {
+			index,
+			target: target.map(Path::to_owned),
+			stash: stash.to_owned(),
+		})
+	}
+	pub(crate) fn created(&mut self, index: usize, path: &Path) -> Result<()> {
+		self.write(&Entry::Created {
+			index,
+			path: path.to_owned(),
+		})
+	}
+	pub(crate) fn completed(&mut self, index: usize) -> Result<()> {
+		self.write(&Entry::Completed { index })
+	}
+	pub(crate) fn undone(&mut self, index: usize) -> Result<()> {
+		self.write(&Entry::Undone { index })
+	}
+	/// The plan is done, remove the journal.
+	pub fn finish(self) -> Result<()> {
+		fs::remove_file(&self.path).map_err(|e| Error::io("remove", &self.path, e))?;
+		Ok(())
+	}
+	/// Append `entry` and wait until it's on disk.
+	fn write(&mut self, entry: &Entry) -> Result<()> {
+		let mut line = serde_json::to_string(entry).context("Failed to serialize journal entry")?;
+		line.push('\n');
+		self.file
+			.write_all(line.as_bytes())
+			.and_then(|_| self.file.sync_data())
+			.map_err(|e| Error::io("write", &self.path, e))?;
+		Ok(())
+	}
+}
+
+/// What to do with an interrupted run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+	/// Undo what was done.
+	Rollback,
+	/// Run the rest of the plan.
+	Finish,
+	/// Leave it for later.
+	Keep,
+}
+
+/// Journal of an interrupted run.
+#[derive(Debug, Clone)]
+pub struct Unfinished {
+	pub path: PathBuf,
+	pub plan: RenamePlan,
+	/// Operations which were started, in order, as they were run.
+	pub started: Vec<(usize, Operation)>,
+	/// Where overwritten targets were moved to, with the entry of a merge they belong to.
+	stashes: Vec<(usize, Option<PathBuf>, PathBuf)>,
+	/// What merges created inside their targets.
+	created: HashMap<usize, Vec<PathBuf>>,
+	completed: HashSet<usize>,
+	undone: HashSet<usize>,
+}
+
+impl Unfinished {
+	/// Journals in `dir` left behind by runs which aren't running anymore. Journals which can't
+	/// be read are skipped with a warning.
+	pub fn find(dir: &Path) -> Result<Vec<Self>> {
+		let entries = match fs::read_dir(dir) {
+			Ok(entries) => entries,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(e) => Err(Error::io("read", dir, e))?,
+		};
+		let mut paths: Vec<PathBuf> = entries
+			.filter_map(|e| e.ok().map(|e| e.path()))
+			.filter(|p| matches!(p.extension(), Some(e) if e == "ndjson"))
+			.collect();
+		paths.sort();
+		let mut out = vec![];
+		for path in paths {
+			if is_running(&path) {
+				continue;
+			}
+			match Self::load(&path) {
+				Ok(journal) => out.push(journal),
+				Err(e) => warn!("Skipping journal {}: {e:#}", path.display()),
+			}
+		}
+		Ok(out)
+	}
+	pub fn load(path: &Path) -> Result<Self> {
+		let file = File::open(path).map_err(|e| Error::io("open", path, e))?;
+		let mut begin = None;
+		let mut started = vec![];
+		let mut stashes = vec![];
+		let mut created: HashMap<usize, Vec<PathBuf>> = HashMap::new();
+		let (mut completed, mut undone) = (HashSet::new(), HashSet::new());
+		for line in BufReader::new(file).lines() {
+			let line = line.map_err(|e| Error::io("read", path, e))?;
+			// The last line may be cut off by the crash.
+			let Ok(entry) = serde_json::from_str::<Entry>(&line) else {
+				warn!("{}: Ignoring broken line {:?}", path.display(), line);
+				continue;
+			};
+			match entry {
+				Entry::Begin { plan, .. } => begin = Some(plan),
+				Entry::Started { index, operation } => {
+					// Retried with another target, the earlier one wasn't touched.
+					if matches!(started.last(), Some((i, _)) if *i == index) {
+						started.pop();
+					}
+					started.push((index, operation));
+				}
+				Entry::Stashed {
+					index,
+					target,
+					stash,
+				} => stashes.push((index, target, stash)),
+				Entry::Created { index, path } => created.entry(index).or_default().push(path),
+				Entry::Completed { index } => {
+					completed.insert(index);
+				}
+				Entry::Undone { index } => {
+					undone.insert(index);
+				}
+			}
+		}
+		let plan = begin.with_context(|| format!("{}: Journal has no plan", path.display()))?;
+		Ok(Self {
+			path: path.to_owned(),
+			plan,
+			started,
+			stashes,
+			created,
+			completed,
+			undone,
+		})
+	}
+	/// Undo every operation which was done and remove the journal.
+	pub fn rollback(self) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		let done = self.done();
+		// The interrupted merge is the newest.
+		err.append_entries(&mut self.remove_created(|i| !done.iter().any(|(d, _)| *d == i)));
+		for (index, op) in done.into_iter().rev() {
+			let Some(target) = &op.target else {
+				continue;
+			};
+			info!("{} -> {}", target.display(), op.source.display());
+			let res = match op.kind {
+				OperationKind::Copy if op.reason == Some(Reason::Merge) => {
+					remove_created(self.created.get(&index).map_or(&[], Vec::as_slice))
+				}
+				OperationKind::Copy | OperationKind::Link(_) => remove_path(target),
+				OperationKind::Swap => swap(target, &op.source),
+				_ => move_noreplace(target, &op.source, &mut copy_options(Preserve::all())),
+			};
+			if let Err(e) = res {
+				err += anyhow::Error::from(e).context(format!("Failed to undo operation {index}"));
+			}
+		}
+		// Put back everything which was overwritten.
+		err.append_entries(&mut self.restore_stashes(|_| true));
+		self.close(err)
+	}
+	/// Run the rest of the plan and remove the journal.
+	///
+	/// With `history` the whole run is recorded there, with what was done before it was
+	/// interrupted.
+	pub fn finish(self, history: Option<&Path>) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		let earlier: Vec<(Operation, Outcome)> = self
+			.done()
+			.into_iter()
+			.filter_map(|(index, op)| {
+				let outcome = Outcome {
+					target: op.target.clone()?,
+					stashes: vec![],
+					created: match op.reason {
+						Some(Reason::Merge) => self.created.get(&index).cloned(),
+						_ => None,
+					},
+				};
+				Some((op.clone(), outcome))
+			})
+			.collect();
+		let done: HashSet<usize> = self.done().into_iter().map(|(i, _)| i).collect();
+		// Merges which didn't complete are started over.
+		err.append_entries(&mut self.remove_created(|i| !done.contains(&i)));
+		// Targets of operations which didn't happen aren't overwritten yet.
+		err.append_entries(&mut self.restore_stashes(|i| !done.contains(&i)));
+		for (index, _, stash) in &self.stashes {
+			if done.contains(index) && stash.symlink_metadata().is_ok() {
+				err.push_result(remove_path(stash));
+			}
+		}
+		if !self.undone.is_empty() {
+			err += anyhow::anyhow!(
+				"{} was interrupted while undoing, it can only be rolled back",
+				self.path.display()
+			);
+			return err;
+		}
+		let last = self.started.last().map(|(i, _)| *i);
+		let mut operations = vec![];
+		if let Some((index, op)) = self.started.last() {
+			// Interrupted in the middle of this one.
+			if !self.completed.contains(index) && !happened(op) {
+				operations.push(Operation {
+					fingerprint: None,
+					..op.clone()
+				});
+			}
+		}
+		operations.extend(
+			self.plan
+				.operations
+				.iter()
+				.skip(last.map_or(0, |i| i + 1))
+				.cloned(),
+		);
+		let plan = RenamePlan {
+			operations,
+			journal: None,
+			history: history.map(Path::to_owned),
+			..self.plan.clone()
+		};
+		err.append_entries(&mut plan.execute_after(&earlier));
+		self.close(err)
+	}
+	/// Move stashed targets of the operations matching `filter` back, newest first.
+	fn restore_stashes(&self, filter: impl Fn(usize) -> bool) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		for (index, entry, stash) in self.stashes.iter().rev() {
+			let op = self.started.iter().rfind(|(i, _)| i == index);
+			let Some(target) = entry.as_ref().or(op.and_then(|(_, op)| op.target.as_ref())) else {
+				continue;
+			};
+			if !filter(*index) || stash.symlink_metadata().is_err() {
+				continue;
+			}
+			info!("{} -> {}", stash.display(), target.display());
+			err.push_result(rename_noreplace(stash, target));
+		}
+		return_ok!((), err);
+	}
+	/// Remove what the merges matching `filter` created.
+	fn remove_created(&self, filter: impl Fn(usize) -> bool) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		for (index, _) in self.started.iter().rev() {
+			if let (true, Some(created)) = (filter(*index), self.created.get(index)) {
+				err.push_result(remove_created(created));
+			}
+		}
+		return_ok!((), err);
+	}
+	/// Operations which were done and not undone yet, in order.
+	fn done(&self) -> Vec<(usize, &Operation)> {
+		let last = self.started.len().saturating_sub(1);
+		self.started
+			.iter()
+			.enumerate()
+			.filter(|(n, (index, op))| {
+				!self.undone.contains(index)
+					&& (self.completed.contains(index) || (*n == last && happened(op)))
+			})
+			.map(|(_, (index, op))| (*index, op))
+			.collect()
+	}
+	fn close(self, mut err: ErrorLogAnyhow<()>) -> ErrorLogAnyhow<()> {
+		match err.entries().is_empty() {
+			true => {
+				err.push_result(
+					fs::remove_file(&self.path).map_err(|e| Error::io("remove", &self.path, e)),
+				);
+			}
+			false => error!("Keeping journal {}", self.path.display()),
+		}
+		return_ok!((), err);
+	}
+}
+
+/// Whether an operation which was interrupted got done.
+fn happened(op: &Operation) -> bool {
+	let Some(target) = &op.target else {
+		return false;
+	};
+	match op.kind {
+		// Merges are only done once they completed.
+		OperationKind::Copy if op.reason == Some(Reason::Merge) => false,
+		// Copies only get their name once they are complete.
+		OperationKind::Copy | OperationKind::Link(_) => target.symlink_metadata().is_ok(),
+		// The source is at the target now, and not at the source anymore.
+		OperationKind::Swap => {
+			let at = |path: &Path| {
+				op.fingerprint.is_some() && Fingerprint::of(path).ok() == op.fingerprint
+			};
+			at(target) && !at(&op.source)
+		}
+		_ => op.source.symlink_metadata().is_err() && target.symlink_metadata().is_ok(),
+	}
+}
+
+/// Whether the run which wrote the journal at `path` is still going, it keeps the file locked.
+fn is_running(path: &Path) -> bool {
+	File::open(path).is_ok_and(|f| matches!(f.try_lock(), Err(TryLockError::WouldBlock)))
+}
@@ -1,11 +1,37 @@
 pub mod args;
+pub mod builder;
+pub mod case;
 pub mod config;
+pub mod error;
+pub mod fs;
 pub mod helper;
+pub mod history;
 pub mod input;
+pub mod journal;
 pub mod log;
 pub mod new_rename;
+pub mod numbering;
+pub mod order;
+pub mod plan;
+pub mod preserve;
+pub mod raw_path;
+pub mod relink;
 pub mod rename;
+pub mod replace;
+pub mod report;
+pub mod template;
+pub mod transform;
+pub mod walk;
 
+pub use builder::*;
+pub use case::*;
 pub use config::*;
+pub use error::*;
 pub use helper::*;
+pub use numbering::*;
+pub use plan::*;
 pub use rename::*;
+pub use replace::*;
+pub use template::*;
+pub use transform::*;
+pub use walk::*;
@@ -4,23 +4,50 @@
 #![warn(clippy::expect_used)]
 
 pub mod args;
+pub mod builder;
+pub mod case;
 pub mod config;
+pub mod error;
 pub mod error_log;
+pub mod fs;
 pub mod helper;
+pub mod history;
 pub mod input;
+pub mod journal;
 pub mod new_rename;
+pub mod numbering;
+pub mod order;
+pub mod plan;
+pub mod preserve;
+pub mod raw_path;
+pub mod relink;
 pub mod rename;
+pub mod replace;
+pub mod report;
+pub mod template;
+pub mod transform;
+pub mod walk;
 
 pub use crate::args::OnConflict;
 pub use crate::config::Config;
+#[allow(unused_imports)]
 pub use crate::error_log::*;
 pub use crate::helper::*;
-pub use crate::input::read_input;
+pub use crate::history::History;
+pub use crate::input::{read_input, recover_ask};
+pub use crate::journal::{Journal, Recovery, Unfinished};
+pub use crate::plan::RenamePlan;
 pub use crate::rename::Rename;
 
+use std::path::{Path, PathBuf};
+
 use anyhow::{Context, Result};
 use clap::ArgMatches;
 use flexi_logger::Logger;
+use log::warn;
+
+use crate::args::OutputFormat;
+use crate::report::write_reports;
 
 // For re-trigger command!() on toml changes
 const _: &str = include_str!("../Cargo.toml");
@@ -32,9 +59,152 @@ fn main() -> Result<()> {
 	let mut conf = Config::read();
 	let m: ArgMatches = args::matches();
 	conf.display_fn_log();
-	Rename::try_new(m, conf.display_ok().unwrap_or(Config::default()))?
-		.rename()
+	let config = conf.display_ok().unwrap_or_default();
+	let journal_dir = Journal::default_dir();
+	if let Some(ref dir) = journal_dir {
+		match Unfinished::find(dir) {
+			Ok(found) if !found.is_empty() && m.subcommand_name() != Some("recover") => warn!(
+				"{} interrupted run(s) found, use 'renameplus recover' to roll back or finish them",
+				found.len()
+			),
+			Err(e) => warn!("Failed to look for interrupted runs: {e:#}"),
+			_ => (),
+		}
+	}
+	match m.subcommand() {
+		Some(("recover", sub)) => recover(sub, journal_dir.as_deref())?,
+		Some(("history", sub)) => history(sub)?,
+		Some(("undo", sub)) => {
+			let id: Option<u64> = sub
+				.try_get_one("id")
+				.context("Failed to get argument \'id\'")?
+				.copied();
+			let dry: bool = *sub
+				.try_get_one("dry")
+				.context("Failed to get argument \'dry\'")?
+				.unwrap_or(&false);
+			History::new(History::default_dir().context("No history directory")?)
+				.undo(id, dry)
+				.display_ok()
+				.context("Failed to undo")?;
+		}
+		Some(("apply", sub)) => {
+			let plan: &PathBuf = sub
+				.try_get_one("plan")
+				.context("Failed to get argument \'plan\'")?
+				.context("No plan given")?;
+			let dry: bool = *sub
+				.try_get_one("dry")
+				.context("Failed to get argument \'dry\'")?
+				.unwrap_or(&false);
+			RenamePlan {
+				journal: journal_dir,
+				history: History::default_dir(),
+				..RenamePlan::load(plan)?
+			}
+			.execute(dry)
+			.display_ok()
+			.context("Failed to apply plan")?;
+		}
+		_ => {
+			let plan_out: Option<PathBuf> = m
+				.try_get_one("plan")
+				.context("Failed to get argument \'plan\'")?
+				.cloned();
+			let swap: Option<Vec<PathBuf>> = m
+				.try_get_many("swap")
+				.context("Failed to get argument \'swap\'")?
+				.map(|v| v.cloned().collect());
+			let output_format: Option<OutputFormat> = m
+				.try_get_one("output-format")
+				.context("Failed to get argument \'output-format\'")?
+				.copied();
+			let rename = Rename::try_new(m, config)?;
+			match (swap.as_deref(), plan_out, output_format) {
+				(Some([a, b]), _, _) => {
+					rename.swap(a, b).display_ok().context("Failed to swap")?;
+				}
+				(_, Some(path), _) => rename.plan()?.save(&path)?,
+				(_, _, Some(format)) => {
+					let mut reports = vec![];
+					let res = rename.rename_reported(&mut reports);
+					write_reports(&reports, format, std::io::stdout().lock())?;
+					res.display_ok().context("Failed to rename")?;
+				}
+				_ => {
+					rename.rename().display_ok().context("Failed to rename")?;
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+fn history(m: &ArgMatches) -> Result<()> {
+	let history = History::new(History::default_dir().context("No history directory")?);
+	let format_time = |t| chrono::DateTime::<chrono::Local>::from(t).format("%Y-%m-%d %H:%M:%S");
+	match m
+		.try_get_one::<u64>("id")
+		.context("Failed to get argument \'id\'")?
+	{
+		Some(&id) => {
+			let batch = history.get(id)?;
+			println!("{} {}", format_time(batch.time), batch.command.join(" "));
+			for done in &batch.operations {
+				println!("{} -> {}", done.source.display(), done.target.display());
+			}
+		}
+		None => {
+			for batch in history.batches()? {
+				println!(
+					"{:>4}  {}  {:>4} files{}  {}",
+					batch.id,
+					format_time(batch.time),
+					batch.operations.len(),
+					match (batch.undone, batch.partially_undone()) {
+						(Some(_), _) => " (undone)",
+						(None, true) => " (partially undone)",
+						(None, false) => "",
+					},
+					batch.command.join(" ")
+				);
+			}
+		}
+	}
+	Ok(())
+}
+
+fn recover(m: &ArgMatches, dir: Option<&Path>) -> Result<()> {
+	let found = match m
+		.try_get_one::<PathBuf>("journal")
+		.context("Failed to get argument \'journal\'")?
+	{
+		Some(path) => vec![Unfinished::load(path)?],
+		None => Unfinished::find(dir.context("No journal directory")?)?,
+	};
+	if found.is_empty() {
+		println!("No interrupted runs found");
+	}
+	let flag = |id: &str| -> Result<bool> {
+		Ok(*m
+			.try_get_one(id)
+			.with_context(|| format!("Failed to get argument \'{id}\'"))?
+			.unwrap_or(&false))
+	};
+	let (rollback, finish) = (flag("rollback")?, flag("finish")?);
+	for journal in found {
+		let recovery = match (rollback, finish) {
+			(true, _) => Recovery::Rollback,
+			(_, true) => Recovery::Finish,
+			_ => recover_ask(&journal)?,
+		};
+		match recovery {
+			Recovery::Rollback => journal.rollback(),
+			Recovery::Finish => journal.finish(History::default_dir().as_deref()),
+			Recovery::Keep => continue,
+		}
 		.display_ok()
-		.context("Failed to rename")?;
+		.context("Failed to recover")?;
+	}
 	Ok(())
 }
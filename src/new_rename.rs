@@ -1,105 +1,237 @@
-use std::path::PathBuf;
+use std::{any::Any, io::Read, path::PathBuf};
 
-use crate::{args::OnConflict, config::Config, rename::Rename};
-use anyhow::{anyhow, ensure, Context, Result};
+use crate::{
+	args::{LinkKind, OnConflict, OutputFormat, SymlinkPolicy},
+	case::{Case, CaseConversion, CaseTarget, WordBoundary},
+	config::Config,
+	error::Error,
+	helper::parse_paths,
+	history::History,
+	journal::Journal,
+	numbering::{Numbering, SortBy},
+	preserve::{Attribute, Preserve},
+	rename::Rename,
+	replace::Replace,
+	template::Template,
+	transform::Step,
+	walk::Walk,
+};
+use anyhow::{ensure, Context, Result};
+use clap::ArgMatches;
 use log::debug;
 
 impl Rename {
+	/// Build from command line arguments, see [`crate::args::matches`].
 	pub fn try_new(m: clap::ArgMatches, config: Config) -> Result<Self> {
 		debug!("Parsing input args");
-		let files: Vec<PathBuf> = m
-			.try_get_many::<PathBuf>("file")?
-			.context("Failed to get files")
-			.map(move |v| v.cloned().collect())?;
-		let output_files: Option<Vec<PathBuf>> = m
-			.try_get_many::<PathBuf>("output-files")
-			.context("failed to get argument \'output-files\'")?
-			.map(move |v| v.cloned().collect());
-		let replace_sets: Vec<String> = {
-			let sets_opt: Option<Vec<_>> = m
-				.try_get_many::<String>("sets")
-				.context("failed to get argument \'sets\'")?
-				.map(move |v| v.cloned().collect());
-			let mut out = vec![];
-			if let Some(sets) = sets_opt {
-				for set in sets {
-					match config.sets.get(&set).is_some() {
-						true => out.push(set),
-						false => Err(anyhow!("Set \"{set}\" not found"))?,
-					}
-				}
-			}
-			out
+		let flag = |id: &str| -> Result<bool> {
+			Ok(*m
+				.try_get_one(id)
+				.with_context(|| format!("Failed to get argument \'{id}\'"))?
+				.unwrap_or(&false))
+		};
+		let mut files: Vec<PathBuf> = m
+			.try_get_many::<PathBuf>("file")
+			.context("Failed to get argument \'file\'")?
+			.map(move |v| v.cloned().collect())
+			.unwrap_or_default();
+		let on_conflict = m
+			.try_get_one::<OnConflict>("on-conflict")
+			.context("Failed to get argument \'on-conflict\'")?
+			.unwrap_or(&OnConflict::Skip)
+			.to_owned();
+		if m.try_get_one::<OutputFormat>("output-format")
+			.context("Failed to get argument \'output-format\'")?
+			.is_some()
+		{
+			ensure!(
+				on_conflict != OnConflict::Ask,
+				"'--on-conflict ask' prints its questions to stdout, it can't be used with '--output-format'"
+			);
+		}
+		if flag("from-stdin")? {
+			ensure!(
+				on_conflict != OnConflict::Ask,
+				"'--on-conflict ask' reads the answers from stdin, it can't be used with '--from-stdin'"
+			);
+			let mut input = vec![];
+			std::io::stdin()
+				.read_to_end(&mut input)
+				.context("Failed to read stdin")?;
+			files.extend(parse_paths(&input, flag("null")?)?);
+		}
+		if let Some(path) = m
+			.try_get_one::<PathBuf>("from-file")
+			.context("Failed to get argument \'from-file\'")?
+		{
+			let input = std::fs::read(path).map_err(|e| Error::io("read", path, e))?;
+			files.extend(parse_paths(&input, flag("null")?)?);
+		}
+		let undo_on_err = flag("undo-on-err")?;
+		let numbering = Numbering {
+			start: *m
+				.try_get_one("number-start")
+				.context("Failed to get argument \'number-start\'")?
+				.unwrap_or(&1),
+			step: *m
+				.try_get_one("number-step")
+				.context("Failed to get argument \'number-step\'")?
+				.unwrap_or(&1),
+			pad: *m
+				.try_get_one("number-pad")
+				.context("Failed to get argument \'number-pad\'")?
+				.unwrap_or(&0),
+			sort: *m
+				.try_get_one::<SortBy>("sort")
+				.context("Failed to get argument \'sort\'")?
+				.unwrap_or(&SortBy::Args),
+			per_dir: flag("per-dir")?,
 		};
-		if let Some(ref o) = output_files {
-			ensure!(o.is_empty(), "Need at least one output file");
-			let output_file_last = o.last().context("output_files is empty")?;
-			if o.len() < files.len() && !output_file_last.exists() {
-				Err(anyhow!("Last entry of array does not exist"))?;
-				ensure!(output_file_last.is_dir(), "Last entry isn't a directory");
+		let mut builder = Rename::builder()
+			.files(files)
+			.config(config)
+			.numbering(numbering)
+			.undo_on_err(undo_on_err)
+			.fragile(flag("fragile")? || undo_on_err)
+			.dry(flag("dry")?)
+			.dirs(flag("dirs")?)
+			.copy(flag("copy")?)
+			.follow_symlinks(flag("follow-symlinks")?)
+			.symlinks(
+				*m.try_get_one::<SymlinkPolicy>("symlinks")
+					.context("Failed to get argument \'symlinks\'")?
+					.unwrap_or(&SymlinkPolicy::Link),
+			)
+			.on_conflict(on_conflict);
+		if let Some(kind) = m
+			.try_get_one::<LinkKind>("link")
+			.context("Failed to get argument \'link\'")?
+		{
+			builder = builder.link(*kind)
+		}
+		if let Some(attributes) = m
+			.try_get_many::<Attribute>("preserve")
+			.context("Failed to get argument \'preserve\'")?
+		{
+			builder = builder.preserve(Preserve::from_attributes(attributes.copied()))
+		}
+		if let Some(dirs) = m
+			.try_get_many::<PathBuf>("fix-links")
+			.context("Failed to get argument \'fix-links\'")?
+		{
+			for dir in dirs {
+				builder = builder.fix_links(dir)
 			}
 		}
-		let undo_on_err: bool = *m
-			.try_get_one("undo-on-err")
-			.context("Failed to get argument \'undo-on-errr\'")?
-			.ok_or(anyhow!("undo_on_err not set"))?;
-		let replace: Vec<(String, String)> = {
-			let mut out: Vec<(String, String)> = vec![];
-			let inputs: Vec<String> = match m
-				.try_get_many::<String>("replace")
-				.context("Failed to get arg replace")?
-			{
-				Some(r) => r.cloned().collect(),
-				None => vec![],
+		if flag("recursive")? {
+			let globs = |id: &str| -> Result<Vec<String>> {
+				Ok(m.try_get_many::<String>(id)
+					.with_context(|| format!("Failed to get argument \'{id}\'"))?
+					.map(|v| v.cloned().collect())
+					.unwrap_or_default())
 			};
-			for inp in inputs {
-				let splits: Vec<&str> = inp.split('/').collect();
-				out.push(match splits.len() {
-					1 => (splits[0].to_string(), "".to_string()),
-					2 => (splits[0].to_string(), splits[1].to_string()),
-					_ => Err(anyhow!("{inp} can only have one or two '/'"))?,
-				})
+			builder = builder.recursive(Walk {
+				include: globs("include")?,
+				exclude: globs("exclude")?,
+				max_depth: m
+					.try_get_one("max-depth")
+					.context("Failed to get argument \'max-depth\'")?
+					.copied(),
+				hidden: flag("hidden")?,
+				gitignore: flag("gitignore")?,
+			});
+		}
+		if let Some(dir) = Journal::default_dir() {
+			builder = builder.journal(dir)
+		}
+		if let Some(dir) = History::default_dir() {
+			builder = builder.history(dir)
+		}
+		if let Some(dir) = m
+			.try_get_one::<PathBuf>("output-dir")
+			.context("Failed to get argument  \'output-dir\'")?
+		{
+			builder = builder.output_dir(dir)
+		}
+		if let Some(files) = m
+			.try_get_many::<PathBuf>("output-files")
+			.context("failed to get argument \'output-files\'")?
+		{
+			builder = builder.output_files(files)
+		}
+		for arg in step_args(&m, flag("regex")?)? {
+			builder = match arg {
+				StepArg::Step(step) => builder.step(step),
+				StepArg::Pipeline(name) => builder.pipeline(name),
 			}
-			out
-		};
-		Ok(Self {
-			undo_on_err,
-			fragile: m
-				.try_contains_id("fragile")
-				.context("failed to get arg \'fragile\'")?
-				|| undo_on_err,
-			dry: m
-				.try_contains_id("dry")
-				.context("Failed to get argument \'dry\'")?
-				|| undo_on_err,
-			dirs: m
-				.try_contains_id("dirs")
-				.context("Failed to get argument \'dirs'")?,
-			suffix: m
-				.try_get_one("suffix")
-				.context("Failed to get argument \'suffix\'")?
-				.cloned(),
-			prefix: m
-				.try_get_one("prefix")
-				.context("Failed to get argument \'prefix\'")?
-				.cloned(),
-			output_dir: m
-				.try_get_one("output-dir")
-				.context("Failed to get argument  \'output-dir\'")?
-				.cloned(),
-			files,
-			copy: m
-				.try_contains_id("copy")
-				.context("Failed to get field \'copy\'")?,
-			on_conflict: m
-				.try_get_one::<OnConflict>("on-conflict")
-				.context("Failed to get argument \'on-conflict\'")?
-				.unwrap_or(&OnConflict::Skip)
-				.to_owned(),
-			output_files,
-			replace,
-			replace_sets,
-			config,
-		})
+		}
+		builder.build()
+	}
+}
+
+enum StepArg {
+	Step(Step),
+	Pipeline(String),
+}
+
+/// Values of argument `id` with their position in the command line.
+fn indexed<T: Any + Clone + Send + Sync>(m: &ArgMatches, id: &str) -> Result<Vec<(usize, T)>> {
+	let values: Vec<T> = match m
+		.try_get_many::<T>(id)
+		.with_context(|| format!("Failed to get argument \'{id}\'"))?
+	{
+		Some(v) => v.cloned().collect(),
+		None => vec![],
+	};
+	let indices: Vec<usize> = m.indices_of(id).map(Iterator::collect).unwrap_or_default();
+	Ok(indices.into_iter().zip(values).collect())
+}
+
+/// Steps in the order the arguments were given.
+fn step_args(m: &ArgMatches, regex: bool) -> Result<Vec<StepArg>> {
+	let mut out: Vec<(usize, StepArg)> = vec![];
+	let mut push = |i: usize, step: Step| out.push((i, StepArg::Step(step)));
+	for (i, text) in indexed::<String>(m, "prefix")? {
+		push(i, Step::Prefix { text })
+	}
+	for (i, text) in indexed::<String>(m, "suffix")? {
+		push(i, Step::Suffix { text })
+	}
+	for (i, template) in indexed::<String>(m, "template")? {
+		push(
+			i,
+			Step::Template {
+				template: Template::parse(&template)?,
+			},
+		)
+	}
+	for (i, replace) in indexed::<String>(m, "replace")? {
+		push(i, Step::Replace(Replace::parse(&replace, regex)?))
+	}
+	for (i, name) in indexed::<String>(m, "sets")? {
+		push(i, Step::Set { name })
+	}
+	let target: CaseTarget = *m
+		.try_get_one("case-target")
+		.context("Failed to get argument \'case-target\'")?
+		.unwrap_or(&CaseTarget::Stem);
+	let boundary: WordBoundary = *m
+		.try_get_one("word-boundary")
+		.context("Failed to get argument \'word-boundary\'")?
+		.unwrap_or(&WordBoundary::Separator);
+	for (i, case) in indexed::<Case>(m, "case")? {
+		push(
+			i,
+			Step::Case(CaseConversion {
+				case,
+				target,
+				boundary,
+			}),
+		)
+	}
+	for (i, name) in indexed::<String>(m, "pipeline")? {
+		out.push((i, StepArg::Pipeline(name)))
 	}
+	out.sort_by_key(|(i, _)| *i);
+	Ok(out.into_iter().map(|(_, s)| s).collect())
 }
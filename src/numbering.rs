@@ -0,0 +1,192 @@
+use std::{
+	cmp::Ordering,
+	collections::HashMap,
+	ffi::OsStr,
+	fmt::Display,
+	fs::Metadata,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::builder::PossibleValue;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Settings for the `{n}` counter of templates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Numbering {
+	pub start: u64,
+	pub step: u64,
+	/// Minimum amount of digits, padded with zeros.
+	pub pad: usize,
+	/// Order in which the files are counted.
+	pub sort: SortBy,
+	/// Restart counting in every directory.
+	pub per_dir: bool,
+}
+
+impl Default for Numbering {
+	fn default() -> Self {
+		Self {
+			start: 1,
+			step: 1,
+			pad: 0,
+			sort: SortBy::Args,
+			per_dir: false,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+	/// Order given on the command line.
+	#[default]
+	Args,
+	/// Natural order of the file names (`2` before `10`).
+	Name,
+	Mtime,
+	Size,
+}
+
+impl Numbering {
+	/// Counter value of every entry of `files`.
+	///
+	/// Files whose sort key can't be read get an error and aren't counted, the others are
+	/// numbered as if they weren't there.
+	pub fn numbers(&self, files: &[PathBuf]) -> Vec<Result<u64>> {
+		let mut out: Vec<Result<u64>> = files.iter().map(|_| Ok(self.start)).collect();
+		let order: Vec<usize> = match self.sort {
+			SortBy::Args => (0..files.len()).collect(),
+			SortBy::Name => {
+				let mut order: Vec<usize> = (0..files.len()).collect();
+				order.sort_by(|&a, &b| {
+					natural_cmp(
+						files[a].file_name().unwrap_or_default(),
+						files[b].file_name().unwrap_or_default(),
+					)
+					.then_with(|| files[a].cmp(&files[b]))
+				});
+				order
+			}
+			SortBy::Mtime => sorted_by_key(files, &mut out, |f| {
+				metadata(f)?
+					.modified()
+					.with_context(|| format!("Failed to get mtime of {}", f.display()))
+			}),
+			SortBy::Size => sorted_by_key(files, &mut out, |f| Ok(metadata(f)?.len())),
+		};
+		let mut counters: HashMap<Option<PathBuf>, u64> = HashMap::new();
+		for i in order {
+			let dir = self.per_dir.then(|| parent_dir(&files[i]));
+			let counter = counters.entry(dir).or_insert(self.start);
+			out[i] = Ok(*counter);
+			*counter = counter.saturating_add(self.step);
+		}
+		out
+	}
+}
+
+/// Indices of `files` sorted by `key`, files without one are left out and get the error in `out`.
+fn sorted_by_key<K: Ord>(
+	files: &[PathBuf],
+	out: &mut [Result<u64>],
+	key: impl Fn(&Path) -> Result<K>,
+) -> Vec<usize> {
+	let mut keyed: Vec<(K, usize)> = vec![];
+	for (i, file) in files.iter().enumerate() {
+		match key(file) {
+			Ok(k) => keyed.push((k, i)),
+			Err(e) => out[i] = Err(e),
+		}
+	}
+	keyed.sort_by(|a, b| a.0.cmp(&b.0));
+	keyed.into_iter().map(|(_, i)| i).collect()
+}
+
+fn metadata(file: &Path) -> Result<Metadata> {
+	Ok(file
+		.metadata()
+		.map_err(|e| Error::io("read metadata of", file, e))?)
+}
+
+fn parent_dir(file: &Path) -> PathBuf {
+	file.canonicalize()
+		.ok()
+		.and_then(|p| p.parent().map(Path::to_path_buf))
+		.or_else(|| file.parent().map(Path::to_path_buf))
+		.unwrap_or_default()
+}
+
+/// Compare names with numbers in them by their numeric value.
+pub fn natural_cmp(a: &OsStr, b: &OsStr) -> Ordering {
+	let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+	let (mut a_iter, mut b_iter) = (a.chars().peekable(), b.chars().peekable());
+	loop {
+		match (a_iter.peek(), b_iter.peek()) {
+			(None, None) => break,
+			(None, Some(_)) => return Ordering::Less,
+			(Some(_), None) => return Ordering::Greater,
+			(Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+				let take_number = |iter: &mut std::iter::Peekable<std::str::Chars>| {
+					let mut out = String::new();
+					while let Some(d) = iter.next_if(char::is_ascii_digit) {
+						out.push(d);
+					}
+					out
+				};
+				let (x, y) = (take_number(&mut a_iter), take_number(&mut b_iter));
+				let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+				let ord = x_trimmed
+					.len()
+					.cmp(&y_trimmed.len())
+					.then_with(|| x_trimmed.cmp(y_trimmed));
+				if ord != Ordering::Equal {
+					return ord;
+				}
+			}
+			(Some(&x), Some(&y)) => {
+				let ord = x.to_lowercase().cmp(y.to_lowercase());
+				if ord != Ordering::Equal {
+					return ord;
+				}
+				a_iter.next();
+				b_iter.next();
+			}
+		}
+	}
+	// Equal ignoring case and leading zeros.
+	a.cmp(&b)
+}
+
+impl SortBy {
+	pub const ALL: [Self; 4] = [Self::Args, Self::Name, Self::Mtime, Self::Size];
+}
+
+impl Display for SortBy {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Args => "Order of the files",
+			Self::Name => "Name",
+			Self::Mtime => "Modification time",
+			Self::Size => "Size",
+		})
+	}
+}
+
+impl clap::ValueEnum for SortBy {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Args => PossibleValue::new("args").help("Order of the arguments"),
+			Self::Name => PossibleValue::new("name").help("Natural order of the file names"),
+			Self::Mtime => PossibleValue::new("mtime").help("Modification time, oldest first"),
+			Self::Size => PossibleValue::new("size").help("Size, smallest first"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&[Self::Args, Self::Name, Self::Mtime, Self::Size]
+	}
+}
@@ -0,0 +1,128 @@
+use std::{
+	cmp::Reverse,
+	collections::{HashMap, HashSet},
+	ffi::OsString,
+	path::{Path, PathBuf},
+};
+
+use crate::plan::{Operation, OperationKind, Reason};
+
+/// Path with `.` components removed, for comparing paths of a plan.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+	path.components().collect()
+}
+
+/// Move operations on directories after the operations on their contents, so the paths of the
+/// contents stay valid until they are done. Otherwise the order is kept.
+pub(crate) fn descendants_first(operations: Vec<Operation>) -> Vec<Operation> {
+	let sources: Vec<PathBuf> = operations.iter().map(|op| normalize(&op.source)).collect();
+	// Position of the last operation on something inside the source, with the depth of the
+	// source to put nested directories in the right order.
+	let keys: Vec<(usize, Reverse<usize>)> = sources
+		.iter()
+		.enumerate()
+		.map(|(i, source)| {
+			let last = sources
+				.iter()
+				.rposition(|other| other != source && other.starts_with(source))
+				.map_or(i, |j| j.max(i));
+			(last, Reverse(source.components().count()))
+		})
+		.collect();
+	let mut operations: Vec<(usize, Operation)> = operations.into_iter().enumerate().collect();
+	operations.sort_by_key(|(i, _)| keys[*i]);
+	operations.into_iter().map(|(_, op)| op).collect()
+}
+
+/// Order moves so every target is free when its turn comes.
+///
+/// Chains (`1 -> 2, 2 -> 3`) are run from the end, cycles (`a -> b, b -> a`) are broken by
+/// moving one file to a temporary name first.
+pub(crate) fn order_moves(operations: Vec<Operation>) -> Vec<Operation> {
+	let by_source: HashMap<PathBuf, usize> = operations
+		.iter()
+		.enumerate()
+		.filter(|(_, op)| op.kind == OperationKind::Move)
+		.map(|(i, op)| (normalize(&op.source), i))
+		.collect();
+	// Move which has to be done before `i`, because it frees the target of `i`.
+	let dependency = |i: usize| -> Option<usize> {
+		let target = operations[i].target.as_ref()?;
+		by_source
+			.get(&normalize(target))
+			.copied()
+			.filter(|&j| j != i)
+	};
+	let mut taken: HashSet<PathBuf> = operations
+		.iter()
+		.flat_map(|op| [Some(&op.source), op.target.as_ref()])
+		.flatten()
+		.map(|p| normalize(p))
+		.collect();
+	let mut done = vec![false; operations.len()];
+	let mut out = Vec::with_capacity(operations.len());
+	for i in 0..operations.len() {
+		if done[i] {
+			continue;
+		}
+		if operations[i].kind != OperationKind::Move {
+			done[i] = true;
+			out.push(operations[i].clone());
+			continue;
+		}
+		// Follow the chain of moves blocking each other.
+		let mut path = vec![i];
+		let mut on_path: HashSet<usize> = HashSet::from([i]);
+		let mut cycle_start = None;
+		while let Some(j) = path.last().and_then(|&k| dependency(k)) {
+			if done[j] {
+				break;
+			}
+			if on_path.contains(&j) {
+				cycle_start = Some(j);
+				break;
+			}
+			path.push(j);
+			on_path.insert(j);
+		}
+		let temp = cycle_start.map(|j| {
+			let temp = temp_name(&operations[j].source, &taken);
+			taken.insert(normalize(&temp));
+			out.push(Operation {
+				target: Some(temp.clone()),
+				reason: Some(Reason::Cycle),
+				..operations[j].clone()
+			});
+			(j, temp)
+		});
+		for &k in path.iter().rev() {
+			done[k] = true;
+			match temp {
+				Some((j, ref temp)) if j == k => out.push(Operation {
+					source: temp.clone(),
+					reason: Some(Reason::Cycle),
+					fingerprint: None,
+					..operations[k].clone()
+				}),
+				_ => out.push(operations[k].clone()),
+			}
+		}
+	}
+	out
+}
+
+/// Unused name next to `source`.
+pub(crate) fn temp_name(source: &Path, taken: &HashSet<PathBuf>) -> PathBuf {
+	let name = source.file_name().unwrap_or_default();
+	let mut i: u32 = 0;
+	loop {
+		let mut temp_name = OsString::from(".");
+		temp_name.push(name);
+		temp_name.push(format!(".renameplus-tmp-{i}"));
+		let temp = source.with_file_name(temp_name);
+		if !taken.contains(&normalize(&temp)) && temp.symlink_metadata().is_err() {
+			return temp;
+		}
+		i += 1;
+	}
+}
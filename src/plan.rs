@@ -0,0 +1,1000 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt::Display,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use error_log::{return_ok, ErrorLogAnyhow};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	args::LinkKind,
+	args::OnConflict,
+	error::{display_paths, Error, ErrorCategory},
+	fs::{copy_with, link, move_noreplace, remove_path, rename_noreplace, swap, CopyOptions},
+	history::History,
+	journal::Journal,
+	order::{descendants_first, normalize, order_moves, temp_name},
+	preserve::Preserve,
+	relink::{absolute, links, relink, Relinked},
+	rename::Rename,
+	report::{Report, Status},
+};
+
+/// Operations of a [`Rename`], made with [`Rename::plan`] and run with [`RenamePlan::execute`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenamePlan {
+	/// Stop at the first error.
+	#[serde(default)]
+	pub fragile: bool,
+	/// Undo all operations if an error occurs.
+	#[serde(default)]
+	pub undo_on_err: bool,
+	/// What to do if a target appears after planning.
+	#[serde(default)]
+	pub on_conflict: OnConflict,
+	/// Metadata to keep with [`OperationKind::Copy`], moves keep all of it.
+	#[serde(default, skip_serializing_if = "Preserve::is_empty")]
+	pub preserve: Preserve,
+	/// Copy what symlinks point to instead of the links.
+	#[serde(default)]
+	pub follow_symlinks: bool,
+	/// Directories whose symlinks are updated when what they point to is moved.
+	#[serde(
+		default,
+		skip_serializing_if = "Vec::is_empty",
+		with = "crate::raw_path::vec"
+	)]
+	pub fix_links: Vec<PathBuf>,
+	#[serde(default)]
+	pub operations: Vec<Operation>,
+	/// Directory to keep a [`Journal`] in while executing, to recover from crashes.
+	#[serde(skip)]
+	pub journal: Option<PathBuf>,
+	/// Directory of the [`History`] to record the batch in.
+	#[serde(skip)]
+	pub history: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+	pub kind: OperationKind,
+	#[serde(with = "crate::raw_path")]
+	pub source: PathBuf,
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "crate::raw_path::option"
+	)]
+	pub target: Option<PathBuf>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reason: Option<Reason>,
+	/// State of the source when the plan was made.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub fingerprint: Option<Fingerprint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+	Move,
+	Copy,
+	Link(LinkKind),
+	/// Exchange source and target.
+	Swap,
+	Skip,
+	/// Target exists, needs to be decided before executing.
+	Conflict,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+	/// Target exists and gets overwritten.
+	Overwrite,
+	/// New name is the same as the old one.
+	Unchanged,
+	/// Renaming directories isn't allowed.
+	Directory,
+	TargetExists,
+	/// Other files of the batch have the same target, contains all of them.
+	Collision(#[serde(with = "crate::raw_path::vec")] Vec<PathBuf>),
+	/// Part of a cycle, moved through a temporary name.
+	Cycle,
+	/// Directory copied onto a directory, the contents are merged.
+	Merge,
+	/// Source doesn't exist.
+	NotFound,
+	/// Failed to get the new name.
+	Failed(String),
+}
+
+impl Reason {
+	/// Why `file` is skipped after `err`.
+	fn of_error(file: &Path, err: &anyhow::Error) -> Self {
+		match Error::find(err) {
+			Some(Error::NotFound { path } | Error::SourceVanished { path }) if path == file => {
+				Self::NotFound
+			}
+			_ => Self::Failed(format!("{err:#}")),
+		}
+	}
+}
+
+/// What [`RenamePlan::execute`] did for an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+	/// Target the operation was run with.
+	pub target: PathBuf,
+	/// Overwritten paths, with the name they were moved to until the batch is done.
+	pub stashes: Vec<(PathBuf, PathBuf)>,
+	/// What a merge created inside the target, in order. `None` if it wasn't merged.
+	pub created: Option<Vec<PathBuf>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+	pub size: u64,
+	pub modified: SystemTime,
+}
+
+impl Fingerprint {
+	pub fn of(path: &Path) -> Result<Self, Error> {
+		let meta = path
+			.symlink_metadata()
+			.map_err(|e| Error::io("read metadata of", path, e))?;
+		Ok(Self {
+			size: meta.len(),
+			modified: meta
+				.modified()
+				.map_err(|e| Error::io("read mtime of", path, e))?,
+		})
+	}
+}
+
+impl Rename {
+	/// Decide what to do with every file, without changing anything.
+	pub fn plan(&self) -> Result<RenamePlan> {
+		// Files with what really gets renamed for them.
+		let previews: Vec<(PathBuf, Result<Option<PathBuf>>)> = self
+			.preview_results()
+			.into_iter()
+			.map(|(file, new)| {
+				let source = self.source(&file).map_or(file.clone(), Cow::into_owned);
+				(source, new)
+			})
+			.collect();
+		// Sources moved away in this batch, their names are free once it's their turn.
+		let moving: HashSet<PathBuf> = match self.operation_kind() {
+			OperationKind::Move => previews
+				.iter()
+				.filter(|(file, new)| matches!(new, Ok(Some(t)) if t != file))
+				.map(|(file, _)| normalize(file))
+				.collect(),
+			_ => HashSet::new(),
+		};
+		let mut operations = vec![];
+		for (file, new) in previews {
+			let mut op = Operation {
+				kind: OperationKind::Skip,
+				source: file.clone(),
+				target: None,
+				reason: None,
+				fingerprint: None,
+			};
+			match new {
+				Err(e) => op.reason = Some(Reason::of_error(&file, &e)),
+				Ok(None) => op.reason = Some(Reason::Directory),
+				Ok(Some(target)) if target == file => {
+					op.reason = Some(Reason::Unchanged);
+					op.target = Some(target);
+				}
+				Ok(Some(target)) => match Fingerprint::of(&file) {
+					Err(e) => op.reason = Some(Reason::of_error(&file, &e.into())),
+					Ok(fingerprint) => {
+						op.fingerprint = Some(fingerprint);
+						(op.kind, op.reason) = match target.symlink_metadata().is_ok()
+							&& !moving.contains(&normalize(&target))
+						{
+							true => self.on_target_exists(&file, &target),
+							false => (self.operation_kind(), None),
+						};
+						op.target = Some(target);
+					}
+				},
+			}
+			operations.push(op);
+		}
+		self.mark_collisions(&mut operations);
+		self.mark_blocked(&mut operations);
+		Ok(self.plan_of(order_moves(descendants_first(operations))))
+	}
+	/// Plan exchanging the names of `a` and `b`.
+	///
+	/// It's one [`OperationKind::Swap`] run with [`crate::fs::swap`], journaled and recorded in
+	/// the history like a rename.
+	pub fn plan_swap(&self, a: &Path, b: &Path) -> Result<RenamePlan> {
+		for path in [a, b] {
+			if path.symlink_metadata().is_err() {
+				Err(Error::NotFound {
+					path: path.to_owned(),
+				})?
+			}
+		}
+		Ok(self.plan_of(vec![Operation {
+			kind: OperationKind::Swap,
+			source: a.to_owned(),
+			target: Some(b.to_owned()),
+			reason: None,
+			fingerprint: Some(Fingerprint::of(a)?),
+		}]))
+	}
+	/// Plan running `operations` with the options of `self`.
+	fn plan_of(&self, operations: Vec<Operation>) -> RenamePlan {
+		RenamePlan {
+			fragile: self.fragile,
+			undo_on_err: self.undo_on_err,
+			on_conflict: self.on_conflict.clone(),
+			preserve: self.preserve,
+			follow_symlinks: self.follow_symlinks,
+			fix_links: self.fix_links.clone(),
+			operations,
+			journal: self.journal.clone(),
+			history: self.history.clone(),
+		}
+	}
+	/// Apply the conflict policy to every group of files with the same target.
+	///
+	/// Files of a batch never overwrite each other, with `--on-conflict overwrite` the group
+	/// is skipped like with `skip`.
+	fn mark_collisions(&self, operations: &mut [Operation]) {
+		let mut groups: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+		for (i, op) in operations.iter().enumerate() {
+			if let (false, Some(target)) = (op.kind == OperationKind::Skip, &op.target) {
+				groups
+					.entry(target.components().collect())
+					.or_default()
+					.push(i);
+			}
+		}
+		for (target, members) in groups.into_iter().filter(|(_, m)| m.len() > 1) {
+			let sources: Vec<PathBuf> = members
+				.iter()
+				.map(|&i| operations[i].source.clone())
+				.collect();
+			warn!(
+				"{} files would be renamed to {}",
+				sources.len(),
+				target.display()
+			);
+			if self.on_conflict == OnConflict::Overwrite {
+				warn!(
+					"Skipped {}: --on-conflict overwrite only replaces files outside of the batch",
+					display_paths(&sources)
+				);
+			}
+			for i in members {
+				operations[i].kind = match self.on_conflict {
+					OnConflict::Ask => OperationKind::Conflict,
+					OnConflict::Skip | OnConflict::Overwrite => OperationKind::Skip,
+				};
+				operations[i].reason = Some(Reason::Collision(sources.clone()));
+			}
+		}
+	}
+	/// Apply the conflict policy to moves whose target is taken by a file which doesn't move
+	/// away, e.g. because it got skipped itself.
+	fn mark_blocked(&self, operations: &mut [Operation]) {
+		loop {
+			let freed: HashSet<PathBuf> = operations
+				.iter()
+				.filter(|op| op.kind == OperationKind::Move)
+				.map(|op| normalize(&op.source))
+				.collect();
+			let mut changed = false;
+			for op in operations.iter_mut() {
+				let Some(ref target) = op.target else {
+					continue;
+				};
+				if matches!(
+					op.kind,
+					OperationKind::Move | OperationKind::Copy | OperationKind::Link(_)
+				) && op.reason.is_none()
+					&& target.symlink_metadata().is_ok()
+					&& !freed.contains(&normalize(target))
+				{
+					(op.kind, op.reason) = self.on_target_exists(&op.source, target);
+					changed = true;
+				}
+			}
+			if !changed {
+				break;
+			}
+		}
+	}
+	/// Kind and reason of an operation whose target exists.
+	///
+	/// Directories copied onto directories are merged, the policy applies to their contents.
+	fn on_target_exists(&self, source: &Path, target: &Path) -> (OperationKind, Option<Reason>) {
+		if self.operation_kind() == OperationKind::Copy
+			&& is_dir(source, self.follow_symlinks)
+			&& is_dir(target, false)
+		{
+			return (OperationKind::Copy, Some(Reason::Merge));
+		}
+		match self.on_conflict {
+			OnConflict::Skip => (OperationKind::Skip, Some(Reason::TargetExists)),
+			OnConflict::Overwrite => (self.operation_kind(), Some(Reason::Overwrite)),
+			OnConflict::Ask => (OperationKind::Conflict, Some(Reason::TargetExists)),
+		}
+	}
+	pub(crate) fn operation_kind(&self) -> OperationKind {
+		match (self.link, self.copy) {
+			(Some(kind), _) => OperationKind::Link(kind),
+			(None, true) => OperationKind::Copy,
+			(None, false) => OperationKind::Move,
+		}
+	}
+}
+
+impl RenamePlan {
+	pub fn to_json(&self) -> Result<String> {
+		serde_json::to_string_pretty(self).context("Failed to serialize plan")
+	}
+	pub fn from_json(json: &str) -> Result<Self> {
+		serde_json::from_str(json).context("Failed to parse plan")
+	}
+	pub fn to_toml(&self) -> Result<String> {
+		toml::to_string_pretty(self).context("Failed to serialize plan")
+	}
+	pub fn from_toml(toml: &str) -> Result<Self> {
+		toml::from_str(toml).context("Failed to parse plan")
+	}
+	/// Write as TOML if `path` ends with `.toml`, else as JSON.
+	pub fn save(&self, path: &Path) -> Result<()> {
+		let content = match is_toml(path) {
+			true => self.to_toml()?,
+			false => self.to_json()?,
+		};
+		std::fs::write(path, content).map_err(|e| Error::io("write", path, e))?;
+		Ok(())
+	}
+	/// Read a plan written by [`Self::save`].
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path).map_err(|e| Error::io("read", path, e))?;
+		match is_toml(path) {
+			true => Self::from_toml(&content),
+			false => Self::from_json(&content),
+		}
+		.with_context(|| format!("Failed to load plan {}", path.display()))
+	}
+	/// Groups of files with the same target.
+	pub fn collisions(&self) -> Vec<Error> {
+		let mut out: BTreeMap<&PathBuf, &Vec<PathBuf>> = BTreeMap::new();
+		for op in &self.operations {
+			if let (Some(target), Some(Reason::Collision(sources))) = (&op.target, &op.reason) {
+				out.insert(target, sources);
+			}
+		}
+		out.into_iter()
+			.map(|(target, sources)| Error::Collision {
+				target: target.clone(),
+				sources: sources.clone(),
+			})
+			.collect()
+	}
+	/// Check that the sources haven't changed since the plan was made.
+	///
+	/// Targets taken in the meantime are handled by [`Self::on_conflict`] while executing.
+	pub fn verify(&self) -> Result<()> {
+		for op in &self.operations {
+			let Some(fingerprint) = op.fingerprint else {
+				continue;
+			};
+			if op.kind == OperationKind::Skip {
+				continue;
+			}
+			if op.source.symlink_metadata().is_err() {
+				Err(Error::SourceVanished {
+					path: op.source.clone(),
+				})?
+			}
+			if Fingerprint::of(&op.source)? != fingerprint {
+				Err(Error::SourceChanged {
+					path: op.source.clone(),
+				})?
+			}
+		}
+		Ok(())
+	}
+	/// Run the operations, with `dry` only print them.
+	///
+	/// Targets which appeared after planning are handled by [`Self::on_conflict`], it can't ask.
+	pub fn execute(&self, dry: bool) -> ErrorLogAnyhow<()> {
+		self.execute_with(dry, refuse)
+	}
+	/// Like [`Self::execute`], `ask` decides about targets which appeared after planning if
+	/// [`Self::on_conflict`] is [`OnConflict::Ask`].
+	pub fn execute_with(
+		&self,
+		dry: bool,
+		ask: impl FnMut(&Path) -> Result<(Option<PathBuf>, OnConflict)>,
+	) -> ErrorLogAnyhow<()> {
+		self.execute_reported(dry, ask, &mut vec![])
+	}
+	/// Like [`Self::execute_with`], adds a [`Report`] for every operation to `reports`, in
+	/// order. Operations which aren't run because of an error are reported too.
+	///
+	/// A file moved through the temporary name of a cycle is reported once, from its old to its
+	/// new name.
+	pub fn execute_reported(
+		&self,
+		dry: bool,
+		ask: impl FnMut(&Path) -> Result<(Option<PathBuf>, OnConflict)>,
+		reports: &mut Vec<Report>,
+	) -> ErrorLogAnyhow<()> {
+		self.run(dry, ask, reports, &[])
+	}
+	/// Like [`Self::execute`] for the rest of an interrupted run, `earlier` were done before the
+	/// interruption. They are recorded in the history together with the new ones.
+	pub(crate) fn execute_after(&self, earlier: &[(Operation, Outcome)]) -> ErrorLogAnyhow<()> {
+		self.run(false, refuse, &mut vec![], earlier)
+	}
+	fn run(
+		&self,
+		dry: bool,
+		mut ask: impl FnMut(&Path) -> Result<(Option<PathBuf>, OnConflict)>,
+		reports: &mut Vec<Report>,
+		earlier: &[(Operation, Outcome)],
+	) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		if let Err(e) = self.verify().context("Plan is outdated") {
+			self.report_all(reports, |op| Report::failed(op, &e));
+			err += e;
+			return err;
+		}
+		let mut journal = match (&self.journal, dry) {
+			(Some(dir), false) => {
+				match Journal::create(dir, self).context("Failed to start journal") {
+					Ok(journal) => Some(journal),
+					Err(e) => {
+						self.report_all(reports, |op| Report::failed(op, &e));
+						err += e;
+						return err;
+					}
+				}
+			}
+			_ => None,
+		};
+		let cycles = self.cycles();
+		// Moves on from temporary names which didn't get the file.
+		let mut skip: HashSet<usize> = HashSet::new();
+		// Row of `reports` of every operation which was done.
+		let mut rows: HashMap<usize, usize> = HashMap::new();
+		let mut done: Vec<(usize, &Operation, Outcome)> = vec![];
+		// Temporary names of cycles, with the original name.
+		let mut temps: HashMap<PathBuf, &PathBuf> = HashMap::new();
+		for (index, op) in self.operations.iter().enumerate() {
+			if skip.contains(&index) {
+				continue;
+			}
+			// The move to the temporary name is reported with the move on from it.
+			let reported = !cycles.contains_key(&index);
+			match self.execute_operation(op, dry, &mut ask, index, &mut journal) {
+				Ok(Some(outcome)) => {
+					if op.reason == Some(Reason::Cycle) && temps.remove(&op.source).is_none() {
+						temps.insert(outcome.target.clone(), &op.source);
+					}
+					if let Some(ref mut j) = journal {
+						err.push_result(j.completed(index));
+					}
+					if reported {
+						rows.insert(index, reports.len());
+						reports.push(Report {
+							target: Some(outcome.target.clone()),
+							..Report::new(&self.reported(index, &cycles), Status::Done)
+						});
+					}
+					done.push((index, op, outcome))
+				}
+				Ok(None) if !reported => (),
+				Ok(None) => reports.push(Report::new(
+					&self.reported(index, &cycles),
+					match dry && op.kind != OperationKind::Skip {
+						true => Status::Planned,
+						false => Status::Skipped,
+					},
+				)),
+				Err(e) => {
+					reports.push(Report::failed(&self.reported(index, &cycles), &e));
+					skip.extend(cycles.get(&index));
+					let category = ErrorCategory::of(&e);
+					err += e.context(format!(
+						"Failed to rename {} ({category})",
+						op.source.display()
+					));
+					// Cancel if error occured and --fragile set.
+					if self.fragile {
+						for rest in index + 1..self.operations.len() {
+							if !skip.contains(&rest) && !cycles.contains_key(&rest) {
+								reports.push(Report::not_run(
+									&self.reported(rest, &cycles),
+									"not run after an earlier error with --fragile",
+								));
+							}
+						}
+						break;
+					}
+				}
+			}
+		}
+		if self.undo_on_err && !err.entries().is_empty() {
+			for (index, op, outcome) in done.into_iter().rev() {
+				match undo_operation(op, &outcome) {
+					Ok(()) => {
+						if let Some(ref mut j) = journal {
+							err.push_result(j.undone(index));
+						}
+						if let Some(&row) = rows.get(&index) {
+							reports[row].status = Status::Undone;
+						}
+					}
+					Err(e) => error!("{:#}", e),
+				}
+				// Put back what it overwrote.
+				restore_stashes(&outcome.stashes);
+			}
+		} else {
+			for (target, stash) in done.iter().flat_map(|(_, _, o)| &o.stashes) {
+				if let Err(e) = remove_path(stash) {
+					warn!(
+						"Failed to remove overwritten {} from {}: {e}",
+						target.display(),
+						stash.display()
+					)
+				}
+			}
+			// Give files stuck at a temporary name their old name back.
+			for (temp, original) in temps {
+				match rename_noreplace(&temp, original) {
+					Ok(()) => done.retain(|(_, _, o)| o.target != temp),
+					Err(e) => {
+						err += anyhow::Error::from(e).context(format!(
+							"{} was left at {}",
+							original.display(),
+							temp.display()
+						))
+					}
+				}
+			}
+			let relinked = self.fix_links(&done, &mut err);
+			if let (Some(dir), false) = (&self.history, done.is_empty() && earlier.is_empty()) {
+				let done: Vec<(&Operation, &Outcome)> = earlier
+					.iter()
+					.map(|(op, o)| (op, o))
+					.chain(done.iter().map(|(_, op, o)| (*op, o)))
+					.collect();
+				match History::new(dir).record(self.on_conflict.clone(), &done, relinked) {
+					Ok(id) => info!("Saved as batch {id}, revert with 'renameplus undo {id}'"),
+					Err(e) => err += e.context("Failed to save history"),
+				}
+			}
+		}
+		if let Some(j) = journal {
+			err.push_result(j.finish());
+		}
+		return_ok!((), err);
+	}
+	/// Add a report made by `report` for every operation, when none of them is run.
+	pub(crate) fn report_all(
+		&self,
+		reports: &mut Vec<Report>,
+		report: impl Fn(&Operation) -> Report,
+	) {
+		let cycles = self.cycles();
+		for index in (0..self.operations.len()).filter(|i| !cycles.contains_key(i)) {
+			reports.push(report(&self.reported(index, &cycles)));
+		}
+	}
+	/// Moves of a file to the temporary name of a cycle, with the move on from there.
+	fn cycles(&self) -> HashMap<usize, usize> {
+		let mut out = HashMap::new();
+		for (i, op) in self.operations.iter().enumerate() {
+			if op.reason != Some(Reason::Cycle) {
+				continue;
+			}
+			let next = self.operations[i + 1..].iter().position(|later| {
+				later.reason == Some(Reason::Cycle) && Some(&later.source) == op.target.as_ref()
+			});
+			if let Some(j) = next {
+				out.insert(i, i + 1 + j);
+			}
+		}
+		out
+	}
+	/// Operation `index` as it's reported, the moves of a cycle go from the old to the new name.
+	fn reported(&self, index: usize, cycles: &HashMap<usize, usize>) -> Cow<'_, Operation> {
+		let op = &self.operations[index];
+		if let Some(&next) = cycles.get(&index) {
+			return Cow::Owned(Operation {
+				target: self.operations[next].target.clone(),
+				..op.clone()
+			});
+		}
+		match cycles.iter().find(|(_, &next)| next == index) {
+			Some((&first, _)) => Cow::Owned(Operation {
+				source: self.operations[first].source.clone(),
+				..op.clone()
+			}),
+			None => Cow::Borrowed(op),
+		}
+	}
+	/// Returns what was done, `None` if nothing.
+	fn execute_operation(
+		&self,
+		op: &Operation,
+		dry: bool,
+		ask: &mut impl FnMut(&Path) -> Result<(Option<PathBuf>, OnConflict)>,
+		index: usize,
+		journal: &mut Option<Journal>,
+	) -> Result<Option<Outcome>> {
+		let mut target = match (op.kind, &op.target) {
+			(OperationKind::Skip, _) => {
+				match op.reason {
+					Some(Reason::NotFound) => Err(Error::NotFound {
+						path: op.source.clone(),
+					})?,
+					Some(Reason::Failed(ref e)) => Err(anyhow!("{e}"))?,
+					ref reason => info!(
+						"{} Skipped: {}",
+						op.source.display(),
+						reason.as_ref().map_or("".to_string(), Reason::to_string)
+					),
+				}
+				return Ok(None);
+			}
+			(OperationKind::Conflict, target) => Err(Error::TargetExists {
+				path: target.clone().unwrap_or_default(),
+			})?,
+			(_, None) => Err(anyhow!("No target for {}", op.source.display()))?,
+			(_, Some(target)) => target.clone(),
+		};
+		info!("{} -> {}", op.source.display(), target.display());
+		if dry {
+			info!("{} Skipped because of \'--dry\'.", op.source.display());
+			return Ok(None);
+		}
+		if op.reason == Some(Reason::Merge) && is_dir(&target, false) {
+			if let Some(j) = journal {
+				j.started(
+					index,
+					&Operation {
+						target: Some(target.clone()),
+						..op.clone()
+					},
+				)?;
+			}
+			let mut outcome = Outcome {
+				target: target.clone(),
+				stashes: vec![],
+				created: Some(vec![]),
+			};
+			if let Err(e) = self.merge(&op.source, &target, index, ask, journal, &mut outcome) {
+				// Like a failed copy, nothing is left behind.
+				if let Err(e) = remove_created(outcome.created.as_deref().unwrap_or_default()) {
+					error!("{:#}", anyhow::Error::from(e))
+				}
+				restore_stashes(&outcome.stashes);
+				return Err(e);
+			}
+			return Ok(Some(outcome));
+		}
+		let mut overwrite = op.reason == Some(Reason::Overwrite);
+		let mut on_conflict = self.on_conflict.clone();
+		loop {
+			if let Some(j) = journal {
+				j.started(
+					index,
+					&Operation {
+						target: Some(target.clone()),
+						reason: match overwrite {
+							true => Some(Reason::Overwrite),
+							false => op.reason.clone(),
+						},
+						..op.clone()
+					},
+				)?;
+			}
+			// Move the target out of the way instead of replacing it, so it can be put back.
+			let stash = match overwrite && target.symlink_metadata().is_ok() {
+				true if !self.replaceable(op.kind, &op.source, &target) => {
+					Err(Error::TargetExists {
+						path: target.clone(),
+					})?
+				}
+				true => {
+					let stash = temp_name(&target, &HashSet::new());
+					if let Some(j) = journal {
+						j.stashed(index, None, &stash)?;
+					}
+					rename_noreplace(&target, &stash)?;
+					Some(stash)
+				}
+				false => None,
+			};
+			let res = match op.kind {
+				OperationKind::Copy => copy_with(&op.source, &target, &mut self.copy_options()),
+				OperationKind::Link(kind) => link(&op.source, &target, kind),
+				OperationKind::Swap => swap(&op.source, &target),
+				// Like `mv`, moves to other filesystems keep all they can.
+				_ => move_noreplace(&op.source, &target, &mut copy_options(Preserve::all())),
+			};
+			if let (Err(_), Some(stash)) = (&res, &stash) {
+				if let Err(e) = rename_noreplace(stash, &target) {
+					error!(
+						"Failed to restore {}, it was left at {}: {e}",
+						target.display(),
+						stash.display()
+					)
+				}
+			}
+			match res {
+				Ok(()) => {
+					let stashes = stash.map(|s| (target.clone(), s)).into_iter().collect();
+					return Ok(Some(Outcome {
+						target,
+						stashes,
+						created: None,
+					}));
+				}
+				// Target appeared after planning.
+				Err(Error::TargetExists { .. }) => {
+					warn!("{} appeared after planning", target.display());
+					match on_conflict {
+						OnConflict::Skip => return Ok(None),
+						OnConflict::Overwrite => overwrite = true,
+						OnConflict::Ask => match ask(&target)? {
+							(Some(p), o) => {
+								target = p;
+								on_conflict = o;
+							}
+							(None, o) => on_conflict = o,
+						},
+					}
+				}
+				Err(e) => Err(e)?,
+			}
+		}
+	}
+	/// Copy the contents of the directory `from` into the existing directory `to`.
+	///
+	/// Directories which exist on both sides are merged, other entries which exist already are
+	/// handled by [`Self::on_conflict`] like the targets of operations.
+	fn merge(
+		&self,
+		from: &Path,
+		to: &Path,
+		index: usize,
+		ask: &mut impl FnMut(&Path) -> Result<(Option<PathBuf>, OnConflict)>,
+		journal: &mut Option<Journal>,
+		outcome: &mut Outcome,
+	) -> Result<()> {
+		let entries = std::fs::read_dir(from).map_err(|e| Error::io("read", from, e))?;
+		for entry in entries {
+			let source = entry.map_err(|e| Error::io("read", from, e))?.path();
+			let mut target = to.join(source.file_name().unwrap_or_default());
+			let mut on_conflict = self.on_conflict.clone();
+			loop {
+				if is_dir(&source, self.follow_symlinks) && is_dir(&target, false) {
+					self.merge(&source, &target, index, ask, journal, outcome)?;
+					break;
+				}
+				if target.symlink_metadata().is_ok() {
+					match on_conflict {
+						OnConflict::Skip => {
+							info!("{} Skipped: {}", source.display(), Reason::TargetExists);
+							break;
+						}
+						OnConflict::Overwrite => {
+							if !self.replaceable(OperationKind::Copy, &source, &target) {
+								Err(Error::TargetExists {
+									path: target.clone(),
+								})?
+							}
+							let stash = temp_name(&target, &HashSet::new());
+							if let Some(j) = journal {
+								j.stashed(index, Some(&target), &stash)?;
+							}
+							rename_noreplace(&target, &stash)?;
+							outcome.stashes.push((target.clone(), stash));
+						}
+						OnConflict::Ask => {
+							match ask(&target)? {
+								(Some(p), o) => (target, on_conflict) = (p, o),
+								(None, o) => on_conflict = o,
+							}
+							continue;
+						}
+					}
+				}
+				if let Some(j) = journal {
+					j.created(index, &target)?;
+				}
+				copy_with(&source, &target, &mut self.copy_options())?;
+				outcome.created.get_or_insert_with(Vec::new).push(target);
+				break;
+			}
+		}
+		Ok(())
+	}
+	/// Whether `target` may be overwritten with what an operation of `kind` makes of `source`:
+	/// a file with a file, a symlink with a symlink. Directories are never overwritten, the stash
+	/// would delete the whole tree once the batch is done.
+	fn replaceable(&self, kind: OperationKind, source: &Path, target: &Path) -> bool {
+		let Ok(target) = target.symlink_metadata() else {
+			return true;
+		};
+		let made = match kind {
+			OperationKind::Link(LinkKind::Symbolic | LinkKind::Relative) => {
+				return target.is_symlink()
+			}
+			OperationKind::Copy if self.follow_symlinks => source.metadata(),
+			_ => source.symlink_metadata(),
+		};
+		made.is_ok_and(|m| {
+			(m.is_file() && target.is_file()) || (m.is_symlink() && target.is_symlink())
+		})
+	}
+	/// Update the symlinks in [`Self::fix_links`] pointing to what was moved.
+	fn fix_links(
+		&self,
+		done: &[(usize, &Operation, Outcome)],
+		err: &mut ErrorLogAnyhow<()>,
+	) -> Vec<Relinked> {
+		if self.fix_links.is_empty() {
+			return vec![];
+		}
+		let moved: Result<Vec<Vec<(PathBuf, PathBuf)>>> = done
+			.iter()
+			.filter(|(_, op, _)| matches!(op.kind, OperationKind::Move | OperationKind::Swap))
+			.map(|(_, op, o)| {
+				let (from, to) = (absolute(&op.source)?, absolute(&o.target)?);
+				Ok(match op.kind {
+					// Links are updated one move after the other, so a swap is a cycle.
+					OperationKind::Swap => {
+						let temp = temp_name(&from, &HashSet::new());
+						vec![(from.clone(), temp.clone()), (to.clone(), from), (temp, to)]
+					}
+					_ => vec![(from, to)],
+				})
+			})
+			.collect();
+		let moved = match moved.map(|m| m.concat()) {
+			Ok(moved) if moved.is_empty() => return vec![],
+			Ok(moved) => moved,
+			Err(e) => {
+				*err += e;
+				return vec![];
+			}
+		};
+		let links = match links(&self.fix_links) {
+			Ok(links) => links,
+			Err(e) => {
+				*err += e.context("Failed to find symlinks");
+				return vec![];
+			}
+		};
+		let mut out = vec![];
+		for link in links {
+			match relink(&link, &moved) {
+				Ok(Some(relinked)) => {
+					info!(
+						"{}: {} -> {}",
+						link.display(),
+						relinked.old.display(),
+						relinked.new.display()
+					);
+					out.push(relinked)
+				}
+				Ok(None) => (),
+				Err(e) => *err += e.context(format!("Failed to update {}", link.display())),
+			}
+		}
+		out
+	}
+	fn copy_options(&self) -> CopyOptions {
+		copy_options(self.preserve).follow_symlinks(self.follow_symlinks)
+	}
+}
+
+/// Answer for targets which appeared after planning when nobody can be asked.
+fn refuse(target: &Path) -> Result<(Option<PathBuf>, OnConflict)> {
+	Err(Error::TargetExists {
+		path: target.to_owned(),
+	})?
+}
+
+fn undo_operation(op: &Operation, outcome: &Outcome) -> Result<()> {
+	let target = &outcome.target;
+	match (op.kind, &outcome.created) {
+		(OperationKind::Copy, Some(created)) => remove_created(created)?,
+		(OperationKind::Copy, None) | (OperationKind::Link(_), _) => remove_path(target)?,
+		(OperationKind::Swap, _) => swap(target, &op.source)?,
+		_ => move_noreplace(target, &op.source, &mut copy_options(Preserve::all()))?,
+	}
+	Ok(())
+}
+
+/// Move overwritten targets back from where they were stashed, newest first.
+fn restore_stashes(stashes: &[(PathBuf, PathBuf)]) {
+	for (target, stash) in stashes.iter().rev() {
+		if let Err(e) = rename_noreplace(stash, target) {
+			error!(
+				"Failed to restore {}, it was left at {}: {e}",
+				target.display(),
+				stash.display()
+			)
+		}
+	}
+}
+
+/// Remove what a merge created, newest first. Paths which are gone already are skipped.
+pub(crate) fn remove_created(created: &[PathBuf]) -> Result<(), Error> {
+	for path in created.iter().rev() {
+		if path.symlink_metadata().is_ok() {
+			remove_path(path)?;
+		}
+	}
+	Ok(())
+}
+
+/// Whether `path` is a directory, or a symlink to one with `follow_symlinks`.
+fn is_dir(path: &Path, follow_symlinks: bool) -> bool {
+	match follow_symlinks {
+		true => path.metadata(),
+		false => path.symlink_metadata(),
+	}
+	.is_ok_and(|m| m.is_dir())
+}
+
+/// Copy keeping `preserve`, logs the progress of large files every 10% and every attribute which
+/// couldn't be kept.
+pub(crate) fn copy_options(preserve: Preserve) -> CopyOptions {
+	let mut reported: Option<(PathBuf, u64)> = None;
+	CopyOptions::new(preserve)
+		.progress(move |file, copied, total| {
+			if total < 64 << 20 {
+				return;
+			}
+			let percent = copied * 100 / total / 10 * 10;
+			if !matches!(reported, Some((ref f, p)) if f == file && p == percent) {
+				info!("{}: {percent}%", file.display());
+				reported = Some((file.to_owned(), percent));
+			}
+		})
+		.lost(|file, attribute, e| warn!("{}: Failed to keep {attribute}: {e}", file.display()))
+}
+
+fn is_toml(path: &Path) -> bool {
+	matches!(path.extension(), Some(e) if e == "toml")
+}
+
+impl Display for Reason {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Overwrite => f.write_str("target gets overwritten"),
+			Self::Unchanged => f.write_str("name doesn't change"),
+			Self::Directory => f.write_str("directory, use '--dirs' to rename directories"),
+			Self::TargetExists => f.write_str("target already exists"),
+			Self::Cycle => f.write_str("part of a cycle, moved through a temporary name"),
+			Self::Merge => f.write_str("target directory exists, the contents are merged"),
+			Self::Collision(sources) => write!(f, "same target as {}", display_paths(sources)),
+			Self::NotFound => f.write_str("file does not exist"),
+			Self::Failed(e) => f.write_str(e),
+		}
+	}
+}
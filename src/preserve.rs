@@ -0,0 +1,147 @@
+use std::{fmt::Display, io, path::Path};
+
+use clap::builder::PossibleValue;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+
+/// Metadata kept when copying, besides the permissions which are always kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preserve {
+	/// Access and modification time.
+	#[serde(default)]
+	pub timestamps: bool,
+	/// Owner and group, usually only possible as root.
+	#[serde(default)]
+	pub ownership: bool,
+	/// Extended attributes.
+	#[serde(default)]
+	pub xattrs: bool,
+}
+
+/// Value of `--preserve`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+	Timestamps,
+	Ownership,
+	Xattrs,
+	All,
+}
+
+impl Preserve {
+	pub fn all() -> Self {
+		Self {
+			timestamps: true,
+			ownership: true,
+			xattrs: true,
+		}
+	}
+	pub fn from_attributes(attributes: impl IntoIterator<Item = Attribute>) -> Self {
+		let mut out = Self::default();
+		for attribute in attributes {
+			match attribute {
+				Attribute::Timestamps => out.timestamps = true,
+				Attribute::Ownership => out.ownership = true,
+				Attribute::Xattrs => out.xattrs = true,
+				Attribute::All => out = Self::all(),
+			}
+		}
+		out
+	}
+	pub fn is_empty(&self) -> bool {
+		*self == Self::default()
+	}
+	/// Copy the metadata of `from` to `to`, returns what couldn't be kept.
+	///
+	/// Symlinks themselves are changed, not what they point to.
+	pub fn apply(&self, from: &Path, to: &Path) -> Vec<(Attribute, io::Error)> {
+		let mut lost = vec![];
+		let meta = match from.symlink_metadata() {
+			Ok(meta) => meta,
+			Err(e) => return vec![(Attribute::All, e)],
+		};
+		if self.ownership {
+			if let Err(e) = set_owner(&meta, to) {
+				lost.push((Attribute::Ownership, e))
+			}
+		}
+		if self.xattrs {
+			if let Err(e) = copy_xattrs(from, to) {
+				lost.push((Attribute::Xattrs, e))
+			}
+		}
+		// Last, the others count as changes.
+		if self.timestamps {
+			let atime = FileTime::from_last_access_time(&meta);
+			let mtime = FileTime::from_last_modification_time(&meta);
+			if let Err(e) = filetime::set_symlink_file_times(to, atime, mtime) {
+				lost.push((Attribute::Timestamps, e))
+			}
+		}
+		lost
+	}
+}
+
+#[cfg(unix)]
+fn set_owner(meta: &std::fs::Metadata, to: &Path) -> io::Result<()> {
+	use std::os::unix::fs::MetadataExt;
+	std::os::unix::fs::lchown(to, Some(meta.uid()), Some(meta.gid()))?;
+	// Changing the owner clears setuid and setgid.
+	if !meta.is_symlink() {
+		std::fs::set_permissions(to, meta.permissions())?;
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_owner(_: &std::fs::Metadata, _: &Path) -> io::Result<()> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"ownership is only supported on unix",
+	))
+}
+
+#[cfg(unix)]
+fn copy_xattrs(from: &Path, to: &Path) -> io::Result<()> {
+	for name in xattr::list(from)? {
+		if let Some(value) = xattr::get(from, &name)? {
+			xattr::set(to, &name, &value)?;
+		}
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_: &Path, _: &Path) -> io::Result<()> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"extended attributes are only supported on unix",
+	))
+}
+
+impl Display for Attribute {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Timestamps => "timestamps",
+			Self::Ownership => "ownership",
+			Self::Xattrs => "extended attributes",
+			Self::All => "metadata",
+		})
+	}
+}
+
+impl clap::ValueEnum for Attribute {
+	fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+		Some(match self {
+			Self::Timestamps => {
+				PossibleValue::new("timestamps").help("Access and modification time")
+			}
+			Self::Ownership => PossibleValue::new("ownership").help("Owner and group"),
+			Self::Xattrs => PossibleValue::new("xattrs").help("Extended attributes"),
+			Self::All => PossibleValue::new("all").help("All of them"),
+		})
+	}
+
+	fn value_variants<'a>() -> &'a [Self] {
+		&[Self::Timestamps, Self::Ownership, Self::Xattrs, Self::All]
+	}
+}
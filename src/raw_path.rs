@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize)]
+struct Bytes<'a> {
+	bytes: &'a [u8],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+	Str(String),
+	Bytes { bytes: Vec<u8> },
+}
+
+/// Write `path` as a string, or as its bytes like `{"bytes": [99, 97, 102, 233]}` if it isn't
+/// valid UTF-8, so no name gets lost. Use with `#[serde(with = "crate::raw_path")]`, or one of
+/// the submodules for containers.
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+	match path.to_str() {
+		Some(path) => serializer.serialize_str(path),
+		None => Bytes {
+			bytes: &to_bytes(path).map_err(S::Error::custom)?,
+		}
+		.serialize(serializer),
+	}
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+	match Repr::deserialize(deserializer)? {
+		Repr::Str(path) => Ok(path.into()),
+		Repr::Bytes { bytes } => from_bytes(bytes).map_err(D::Error::custom),
+	}
+}
+
+/// Raw bytes of `path`, only possible on unix.
+pub fn to_bytes(path: &Path) -> Result<Vec<u8>, String> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::ffi::OsStrExt;
+		Ok(path.as_os_str().as_bytes().to_vec())
+	}
+	#[cfg(not(unix))]
+	{
+		Err(format!("{} isn't valid unicode", path.display()))
+	}
+}
+
+/// Path made of raw bytes, only possible on unix.
+pub fn from_bytes(bytes: Vec<u8>) -> Result<PathBuf, String> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::ffi::OsStringExt;
+		Ok(std::ffi::OsString::from_vec(bytes).into())
+	}
+	#[cfg(not(unix))]
+	{
+		String::from_utf8(bytes)
+			.map(PathBuf::from)
+			.map_err(|e| format!("Path isn't valid unicode: {e}"))
+	}
+}
+
+struct Ref<'a>(&'a Path);
+
+impl Serialize for Ref<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serialize(self.0, serializer)
+	}
+}
+
+struct Owned(PathBuf);
+
+impl<'de> Deserialize<'de> for Owned {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserialize(deserializer).map(Self)
+	}
+}
+
+/// For `Option<PathBuf>`.
+pub mod option {
+	use super::*;
+
+	pub fn serialize<S: Serializer>(
+		path: &Option<PathBuf>,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		path.as_deref().map(Ref).serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Option<PathBuf>, D::Error> {
+		Ok(Option::<Owned>::deserialize(deserializer)?.map(|p| p.0))
+	}
+}
+
+/// For `Vec<PathBuf>`.
+pub mod vec {
+	use super::*;
+
+	pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_seq(paths.iter().map(|p| Ref(p)))
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Vec<PathBuf>, D::Error> {
+		Ok(Vec::<Owned>::deserialize(deserializer)?
+			.into_iter()
+			.map(|p| p.0)
+			.collect())
+	}
+}
+
+/// For `Option<Vec<PathBuf>>`.
+pub mod option_vec {
+	use super::*;
+
+	pub fn serialize<S: Serializer>(
+		paths: &Option<Vec<PathBuf>>,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		paths
+			.as_ref()
+			.map(|paths| paths.iter().map(|p| Ref(p)).collect::<Vec<_>>())
+			.serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Option<Vec<PathBuf>>, D::Error> {
+		Ok(Option::<Vec<Owned>>::deserialize(deserializer)?
+			.map(|paths| paths.into_iter().map(|p| p.0).collect()))
+	}
+}
@@ -0,0 +1,122 @@
+use std::{
+	collections::HashSet,
+	path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	error::Error,
+	fs::{relative_path, symlink},
+	order::temp_name,
+	walk::Walk,
+};
+
+/// Symlink rewritten by [`relink`] because what it pointed to was renamed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relinked {
+	#[serde(with = "crate::raw_path")]
+	pub link: PathBuf,
+	/// Target as it was stored in the link.
+	#[serde(with = "crate::raw_path")]
+	pub old: PathBuf,
+	#[serde(with = "crate::raw_path")]
+	pub new: PathBuf,
+	/// Pointed back by an undo which failed for other parts of the batch.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub undone: bool,
+}
+
+/// Symlinks below `scopes`, hidden ones included.
+pub fn links(scopes: &[PathBuf]) -> Result<Vec<PathBuf>> {
+	let walk = Walk {
+		hidden: true,
+		..Default::default()
+	};
+	Ok(walk
+		.files(scopes, false)?
+		.into_iter()
+		.filter(|p| p.symlink_metadata().is_ok_and(|m| m.is_symlink()))
+		.collect())
+}
+
+/// Point `link` to where its target was moved, `moved` are absolute sources and targets in the
+/// order they were moved. Returns `None` if its target wasn't moved.
+///
+/// Paths are compared without resolving symlinks, relative links stay relative.
+pub fn relink(link: &Path, moved: &[(PathBuf, PathBuf)]) -> Result<Option<Relinked>> {
+	let link = absolute(link)?;
+	let dir = link.parent().unwrap_or(Path::new("/"));
+	let old = std::fs::read_link(&link).map_err(|e| Error::io("read", &link, e))?;
+	let target = clean(&dir.join(&old));
+	let mut now = target.clone();
+	for (from, to) in moved {
+		if let Ok(rest) = now.strip_prefix(from) {
+			now = match rest.as_os_str().is_empty() {
+				true => to.clone(),
+				false => to.join(rest),
+			};
+		}
+	}
+	if now == target {
+		return Ok(None);
+	}
+	let new = match old.is_relative() {
+		true => relative_path(dir, &now),
+		false => now.clone(),
+	};
+	replace_link(&link, &new, now.is_dir())?;
+	Ok(Some(Relinked {
+		link,
+		old,
+		new,
+		undone: false,
+	}))
+}
+
+impl Relinked {
+	/// Point the link back to its old target, if it wasn't changed since.
+	pub fn undo(&self) -> Result<()> {
+		let current =
+			std::fs::read_link(&self.link).map_err(|e| Error::io("read", &self.link, e))?;
+		if current != self.new {
+			Err(Error::TargetChanged {
+				path: self.link.clone(),
+			})?
+		}
+		replace_link(&self.link, &self.old, self.link.is_dir())
+	}
+}
+
+/// Replace the symlink `link` in one step.
+fn replace_link(link: &Path, target: &Path, is_dir: bool) -> Result<()> {
+	let temp = temp_name(link, &HashSet::new());
+	symlink(target, &temp, is_dir).map_err(|e| Error::io("create", &temp, e))?;
+	if let Err(e) = std::fs::rename(&temp, link) {
+		let _ = std::fs::remove_file(&temp);
+		Err(Error::io("replace", link, e))?
+	}
+	Ok(())
+}
+
+/// `path` joined to the current dir, with `.` and `..` removed.
+pub fn absolute(path: &Path) -> Result<PathBuf> {
+	let cwd = std::env::current_dir().context("Failed to get current dir")?;
+	Ok(clean(&cwd.join(path)))
+}
+
+/// `path` with `.` and `..` removed without looking at the filesystem.
+fn clean(path: &Path) -> PathBuf {
+	let mut out = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => (),
+			Component::ParentDir => {
+				out.pop();
+			}
+			c => out.push(c),
+		}
+	}
+	out
+}
@@ -1,16 +1,24 @@
-use bstr::ByteSlice;
 use error_log::{return_ok, try_add, ErrorLogAnyhow};
-use format as f;
-use log::{error, info, warn};
-use snake_helper::{unwrap_or_print_err, unwrap_some_or};
-use std::ffi::OsString;
-use std::fs::{copy, rename};
-use std::path::{Path, PathBuf};
+use log::warn;
+use snake_helper::unwrap_or_print_err;
+use std::{
+	borrow::Cow,
+	path::{Path, PathBuf},
+	sync::OnceLock,
+};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
 
-use crate::args::OnConflict;
+use crate::args::{LinkKind, OnConflict, SymlinkPolicy};
 use crate::config::Config;
+use crate::error::Error;
+use crate::numbering::Numbering;
+use crate::order::normalize;
+use crate::plan::{Operation, OperationKind, Reason};
+use crate::preserve::Preserve;
+use crate::replace::Replace;
+use crate::report::Report;
+use crate::transform::{FileName, Step, Transform, TransformContext};
 
 #[derive(Debug, Clone, Default)]
 pub struct Rename {
@@ -18,94 +26,145 @@ pub struct Rename {
 	pub dry: bool,
 	pub dirs: bool,
 	pub files: Vec<PathBuf>,
-	pub prefix: Option<String>,
-	pub suffix: Option<String>,
+	/// Changes to the name, applied in order.
+	pub steps: Vec<Step>,
+	/// Counter used by `{n}` in templates.
+	pub numbering: Numbering,
 	pub copy: bool,
 	pub on_conflict: OnConflict,
 	pub fragile: bool,
 	pub output_dir: Option<PathBuf>,
 	pub output_files: Option<Vec<PathBuf>>,
-	pub replace: Vec<(String, String)>,
-	pub replace_sets: Vec<String>,
 	pub config: Config,
+	/// Directory for the [`crate::journal::Journal`] of a run.
+	pub journal: Option<PathBuf>,
+	/// Directory for the [`crate::history::History`] of completed batches.
+	pub history: Option<PathBuf>,
+	/// Metadata to keep with `--copy`, moves to other filesystems keep all of it.
+	pub preserve: Preserve,
+	/// Copy what symlinks point to instead of the links.
+	pub follow_symlinks: bool,
+	/// Make links instead of moving or copying.
+	pub link: Option<LinkKind>,
+	/// Whether symlinks or what they point to are renamed.
+	pub symlinks: SymlinkPolicy,
+	/// Directories whose symlinks are updated when what they point to is renamed.
+	pub fix_links: Vec<PathBuf>,
+	counted: NumberCache,
 }
 
+/// Counter values of [`Rename::files`] for [`Rename::get_new_path`].
+#[derive(Debug, Clone, Default)]
+struct NumberCache(OnceLock<Counted>);
+
+/// Only used while the files, the numbering and whether a step counts are the same.
 #[derive(Debug, Clone)]
-pub struct RenameOut {
-	original: PathBuf,
-	new_path: Option<PathBuf>,
+struct Counted {
+	files: Vec<PathBuf>,
+	numbering: Numbering,
+	counter: bool,
+	/// `None` for files which can't be numbered.
+	numbers: Vec<Option<u64>>,
 }
 
 impl Rename {
+	/// New path of `file`.
+	///
+	/// The files are numbered for the counter on the first call, later calls reuse the numbers.
 	pub fn get_new_path(&self, file: &Path) -> Result<Option<PathBuf>> {
-		if !file.try_exists().context("Error parsing file")? {
-			Err(anyhow!("File {} does not exist", file.display()))?;
-		}
-		let path: Result<PathBuf> = {
-			match file.file_name() {
-				None => match (file.parent(), self.dirs) {
-					(None, false) => None,
-					// Cancel if file is a folder and --dirs not set.
-					(Some(p), false) => {
-						warn!(
-							"Skipped {} bevause it is a Directory. Use \'-r\' or \'--dirs\' to rename directories",
-							p.display()
-						);
-						return Ok(None);
-					}
-					(p, true) => p.map(|f| f.to_path_buf()),
-				},
-				o => o.map(|f| f.into()),
-			}
-			.context(format!("Failed to parse path {}", file.display()))
-		};
-		let (mut new_name, ext): (OsString, OsString) = {
-			let out = path?;
-			(
-				out.file_stem()
-					.unwrap_or(out.file_name().context("Failed to get file name or stem")?)
-					.to_owned(),
-				out.extension()
-					.map(|e| {
-						let mut out = OsString::from(".");
-						out.push(e);
-						out
-					})
-					.unwrap_or(OsString::from("")),
-			)
+		let n = match self.files.iter().position(|f| f == file) {
+			Some(i) => match self.counted()[i] {
+				Some(n) => n,
+				// Errors aren't kept, get the one of this file again.
+				None => self.numbering.numbers(&[file.to_owned()]).swap_remove(0)?,
+			},
+			None => self.numbering.start,
 		};
-
-		if let Some(p) = &self.prefix {
-			let mut out: OsString = p.into();
-			out.push(&new_name);
-			new_name = out;
+		self.get_new_path_numbered(file, n)
+	}
+	/// [`Self::numbers`] without the errors, from the cache if nothing changed.
+	fn counted(&self) -> Cow<'_, [Option<u64>]> {
+		let counter = self.steps.iter().any(Transform::uses_counter);
+		let numbers = || self.numbers().into_iter().map(Result::ok).collect();
+		match self.counted.0.get() {
+			Some(c)
+				if c.files == self.files
+					&& c.numbering == self.numbering
+					&& c.counter == counter =>
+			{
+				Cow::Borrowed(&c.numbers)
+			}
+			Some(_) => Cow::Owned(numbers()),
+			None => {
+				let numbers = numbers();
+				let c = self.counted.0.get_or_init(|| Counted {
+					files: self.files.clone(),
+					numbering: self.numbering.clone(),
+					counter,
+					numbers,
+				});
+				Cow::Borrowed(&c.numbers)
+			}
 		}
-
-		if let Some(s) = &self.suffix {
-			new_name.push(s);
+	}
+	/// Counter value for every entry of `self.files`.
+	fn numbers(&self) -> Vec<Result<u64>> {
+		match self.steps.iter().any(Transform::uses_counter) {
+			true => self.numbering.numbers(&self.files),
+			false => self
+				.files
+				.iter()
+				.map(|_| Ok(self.numbering.start))
+				.collect(),
 		}
-
-		for (search, replace) in &self.replace {
-			do_replace(&mut new_name, search, replace)
+	}
+	/// Like [`Self::get_new_path`] with `n` as value of the counter.
+	pub fn get_new_path_numbered(&self, file: &Path, n: u64) -> Result<Option<PathBuf>> {
+		let file = self.source(file)?;
+		// Cancel if file is a folder and --dirs not set.
+		if file.symlink_metadata().is_ok_and(|m| m.is_dir()) && !self.dirs {
+			warn!(
+				"Skipped {} bevause it is a Directory. Use \'-r\' or \'--dirs\' to rename directories",
+				file.display()
+			);
+			return Ok(None);
 		}
-		for set_name in &self.replace_sets {
-			let set = &self
-				.config
-				.sets
-				.get(set_name)
-				.context(format!("Set {set_name} not found"))?;
-			for search in &set.set.search {
-				do_replace(&mut new_name, search, &set.set.replace)
-			}
+		let path: PathBuf = file
+			.file_name()
+			.ok_or_else(|| Error::InvalidPath {
+				path: file.to_path_buf(),
+			})?
+			.into();
+		let mut name = FileName::from_path(&path).context("Failed to get file name or stem")?;
+		let ctx = TransformContext {
+			path: &file,
+			n,
+			pad: self.numbering.pad,
+			config: &self.config,
+		};
+		for step in &self.steps {
+			step.apply(&mut name, &ctx)?
 		}
 		let parent = self
-			.get_parent(file)
+			.get_parent(&file)
 			.context("Failed to get parent of path")?;
 		let mut out: PathBuf = parent;
-		new_name.push(ext);
-		out.push(&new_name);
+		out.push(name.joined());
 		Ok(Some(out))
 	}
+	/// What gets renamed for `file`, depending on [`Self::symlinks`].
+	pub(crate) fn source<'a>(&self, file: &'a Path) -> Result<Cow<'a, Path>, Error> {
+		let meta = file
+			.symlink_metadata()
+			.map_err(|e| Error::io("access", file, e))?;
+		match self.symlinks == SymlinkPolicy::Target && meta.is_symlink() {
+			true => Ok(file
+				.canonicalize()
+				.map_err(|e| Error::io("resolve", file, e))?
+				.into()),
+			false => resolve(file),
+		}
+	}
 	fn get_parent(&self, file: &Path) -> Result<PathBuf> {
 		match &self.output_dir {
 			// get parent if no output dir set.
@@ -116,134 +175,146 @@ impl Rename {
 			o => o.to_owned().context("Failed to get output dir"),
 		}
 	}
+	/// New path of every file, after all renames of the batch.
+	///
+	/// Files inside renamed directories are shown in the renamed directory.
 	pub fn preview(&self) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
 		let mut out: Vec<(PathBuf, Option<PathBuf>)> = vec![];
-		for file in &self.files {
-			let new = unwrap_or_print_err!(self.get_new_path(file), continue);
-			out.push((file.clone(), new))
+		for (file, new) in self.preview_results() {
+			let new = unwrap_or_print_err!(new, continue);
+			out.push((file, new))
+		}
+		if self.operation_kind() == OperationKind::Move {
+			final_paths(&mut out);
 		}
 		Ok(out)
 	}
+	/// New path of every file like [`Self::get_new_path`], with the errors of single files.
+	///
+	/// The files are numbered once for all of them.
+	pub fn preview_results(&self) -> Vec<(PathBuf, Result<Option<PathBuf>>)> {
+		self.files
+			.iter()
+			.zip(self.numbers())
+			.map(|(file, n)| {
+				let new = n.and_then(|n| self.get_new_path_numbered(file, n));
+				(file.clone(), new)
+			})
+			.collect()
+	}
 	pub fn push_replace(&mut self, search: impl Into<String>, replace: impl Into<String>) {
-		self.replace.push((search.into(), replace.into()))
+		self.steps
+			.push(Step::Replace(Replace::new(search, replace)))
+	}
+	pub fn push_step(&mut self, step: Step) {
+		self.steps.push(step)
 	}
+	/// Plan and execute the operations.
 	pub fn rename(&self) -> ErrorLogAnyhow<()> {
-		let mut history: Vec<RenameOut> = vec![];
+		self.rename_reported(&mut vec![])
+	}
+	/// Like [`Self::rename`], adds a [`Report`] for every operation to `reports`.
+	pub fn rename_reported(&self, reports: &mut Vec<Report>) -> ErrorLogAnyhow<()> {
 		let mut err = ErrorLogAnyhow::new();
-		if let Some(preview) = err.push_result(self.preview()) {
-			for (file, new_path) in preview {
-				let new_path = unwrap_some_or!(new_path, continue);
-				let curr_out = self.rename_file(&file, new_path);
-				match (curr_out, self.fragile, self.undo_on_err) {
-					// Cancel if error occured and --fragile set.
-					(Err(e), true, _) => {
-						try_add!(Err(e), err);
-					}
-					// Print error if --fragile not set.
-					(Err(e), false, _) => err += e,
-					// Push result to $history if moved and --und_on_err set.
-					(Ok(r), _, true) => {
-						r.new_path.is_some().then(|| history.push(r));
-					}
-					_ => (),
+		let mut plan = match self.plan() {
+			Ok(plan) => plan,
+			Err(e) => {
+				for file in &self.files {
+					let op = Operation {
+						kind: self.operation_kind(),
+						source: file.clone(),
+						target: None,
+						reason: None,
+						fingerprint: None,
+					};
+					reports.push(Report::failed(&op, &e));
 				}
+				err += e;
+				return err;
 			}
+		};
+		for collision in plan.collisions() {
+			err.push_err(collision);
 		}
-		if self.undo_on_err && !err.entries().is_empty() {
-			for entry in history {
-				let new_path = match entry.new_path.context("Path not set") {
-					Err(f) => {
-						err.push_err(f);
-						continue;
-					}
-					//
-					Ok(p) => p,
-				};
-				if let Err(e) = match self.copy {
-					true => std::fs::remove_file(new_path).context("Failed to remove file"),
-					false => {
-						std::fs::rename(new_path, entry.original).context("Failed to rename file")
-					}
-				} {
-					error!("{}", e)
+		if self.fragile && !err.entries().is_empty() {
+			// Nothing is run, the colliding files are the ones which failed.
+			plan.report_all(reports, |op| match (&op.target, &op.reason) {
+				(Some(target), Some(Reason::Collision(sources))) => {
+					let e = Error::Collision {
+						target: target.clone(),
+						sources: sources.clone(),
+					};
+					Report::failed(op, &e.into())
 				}
-			}
+				_ => Report::not_run(op, "not run because of colliding files with --fragile"),
+			});
+			return err;
 		}
+		if let Err(e) = self.resolve_conflicts(&mut plan) {
+			plan.report_all(reports, |op| Report::failed(op, &e));
+			err += e;
+			return err;
+		}
+		err.append_entries(&mut plan.execute_reported(
+			self.dry,
+			|target| self.conflict_ask(&target.to_path_buf()),
+			reports,
+		));
 		return_ok!((), err);
 	}
-	fn rename_file(&self, file: &PathBuf, mut new_path: PathBuf) -> Result<RenameOut> {
-		if new_path.exists() {
-			let mut on_conflict: OnConflict = self.on_conflict.clone();
-			loop {
-				match on_conflict {
-					OnConflict::Ask => match self.conflict_ask(&new_path) {
-						Ok((Some(p), o)) => {
-							on_conflict = o;
-							new_path = p;
-							continue;
-						}
-						Ok((None, o)) => {
-							on_conflict = o;
-							continue;
-						}
-						Err(e) => {
-							Err(e)?;
-						}
-					},
-					OnConflict::Skip => {
-						return Ok(RenameOut {
-							original: file.to_owned(),
-							new_path: None,
-						})
-					}
-					_ => (),
-				}
-				break;
-			}
-		}
-		info!("{} -> {}", file.display(), new_path.display());
-		match (self.dry, self.copy) {
-			(false, true) => {
-				copy(file, &new_path).context(f!("Failed to copy file {}", file.display()))?;
-			}
-			(false, false) => {
-				rename(file, &new_path).context(f!("Failed to rename file {}", file.display()))?
-			}
-			(true, _) => info!(
-				"{} Skipped because of \'--dry\' or \'--copy \'.",
-				file.display()
-			),
-		}
-		Ok(RenameOut {
-			original: file.to_owned(),
-			new_path: Some(new_path),
-		})
+	/// Exchange the names of `a` and `b`, see [`Self::plan_swap`].
+	pub fn swap(&self, a: &Path, b: &Path) -> ErrorLogAnyhow<()> {
+		let mut err = ErrorLogAnyhow::new();
+		let plan = try_add!(self.plan_swap(a, b), err);
+		err.append_entries(&mut plan.execute(self.dry));
+		return_ok!((), err);
 	}
 	pub fn verify_output_dir(&self) -> Result<&Self> {
 		if let Some(o) = &self.output_dir {
 			if !o.exists() {
-				Err(anyhow!("Output dir {} doesn\'t exist", o.display()))?;
+				Err(Error::OutputDirMissing { path: o.clone() })?;
 			}
 		}
 		Ok(self)
 	}
 }
-fn do_replace(str: &mut OsString, search: &str, replace: &str) {
-	*str = {
-		#[cfg(all(not(windows), not(unix)))]
-		{
-			error!("search and replace not supported for your os");
-			break;
-		}
-		#[cfg(unix)]
-		{
-			use std::os::unix::ffi::{OsStrExt, OsStringExt};
-			OsString::from_vec(str.as_bytes().replace(search, replace))
-		}
-		#[cfg(windows)]
-		{
-			use std::os::windows::ffi::{OsStrExt, OsStringExt};
-			OsString::from_vec(str.as_bytes().replace(search, replace))
+
+/// Paths like `..` have no name, use the path they point to.
+pub(crate) fn resolve(file: &Path) -> Result<Cow<'_, Path>, Error> {
+	Ok(match file.file_name() {
+		Some(_) => file.into(),
+		None => file
+			.canonicalize()
+			.map_err(|e| Error::io("resolve", file, e))?
+			.into(),
+	})
+}
+
+/// Move new paths inside renamed directories to the new name of the directory.
+fn final_paths(previews: &mut [(PathBuf, Option<PathBuf>)]) {
+	let mut by_depth: Vec<usize> = (0..previews.len()).collect();
+	by_depth.sort_by_key(|&i| normalize(&previews[i].0).components().count());
+	// Directories with their final path, shallow ones first.
+	let mut moved: Vec<(PathBuf, PathBuf)> = vec![];
+	for i in by_depth {
+		let (file, Some(new)) = &previews[i] else {
+			continue;
+		};
+		let (file, new) = (normalize(file), normalize(new));
+		let ancestor = moved
+			.iter()
+			.filter(|(dir, _)| new.starts_with(dir) && file != *dir)
+			.max_by_key(|(dir, _)| dir.components().count());
+		let new = match ancestor.map(|(dir, to)| (new.strip_prefix(dir), to)) {
+			Some((Ok(rest), to)) => {
+				let new = to.join(rest);
+				previews[i].1 = Some(new.clone());
+				new
+			}
+			_ => new,
+		};
+		if new != file {
+			moved.push((file, new));
 		}
 	}
 }
@@ -0,0 +1,345 @@
+use std::{borrow::Cow, ffi::OsString, str::FromStr, sync::OnceLock};
+
+use anyhow::{anyhow, Context, Result};
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::helper::map_os_bytes;
+
+/// A single search and replace rule.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Replace {
+	pub search: String,
+	pub replace: String,
+	/// Treat `search` as regular expression.
+	/// Capture groups can be used in `replace` with `$1` or `${name}`.
+	#[serde(default)]
+	pub regex: bool,
+	#[serde(default)]
+	pub ignore_case: bool,
+	/// Which matches get replaced.
+	#[serde(default)]
+	pub occurrence: Occurrence,
+	#[serde(skip)]
+	compiled: RegexCache,
+}
+
+/// Regex of a [`Replace`] with the search term and flags it was built from, so it isn't built
+/// again for every name. Only used while they are the same.
+#[derive(Debug, Clone, Default)]
+struct RegexCache(OnceLock<(String, bool, bool, Regex)>);
+
+// Only a cache, equal rules are equal whether they were used or not.
+impl PartialEq for RegexCache {
+	fn eq(&self, _: &Self) -> bool {
+		true
+	}
+}
+
+impl Eq for RegexCache {}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Occurrence {
+	#[default]
+	All,
+	First,
+	Last,
+	/// Only the n-th match, starting at 1.
+	Nth(usize),
+}
+
+impl Replace {
+	pub fn new(search: impl Into<String>, replace: impl Into<String>) -> Self {
+		Self {
+			search: search.into(),
+			replace: replace.into(),
+			..Default::default()
+		}
+	}
+	pub fn new_regex(search: impl Into<String>, replace: impl Into<String>) -> Self {
+		Self {
+			regex: true,
+			..Self::new(search, replace)
+		}
+	}
+	/// Parse a replace expression.
+	///
+	/// Accepts `search/replace` (every match is replaced) and sed style
+	/// `s<d>search<d>replace<d>flags` with any punctuation as delimiter `<d>`.
+	/// The delimiter can be escaped with `\`. Flags:
+	/// - `g`: replace all matches (default: only the first)
+	/// - `N`: only replace the N-th match
+	/// - `l`: only replace the last match
+	/// - `i`: ignore case
+	/// - `r`: treat search as regular expression
+	///
+	/// `regex` sets the default for the `r` flag.
+	pub fn parse(expr: &str, regex: bool) -> Result<Self> {
+		let chars: Vec<(usize, char)> = expr.chars().enumerate().collect();
+		let (out, search_pos) = match chars.get(..2) {
+			Some(&[(_, 's'), (_, delim)]) if is_delimiter(delim) => {
+				(parse_sed(expr, &chars, regex)?, 2)
+			}
+			_ => (parse_simple(expr, &chars, regex)?, 0),
+		};
+		if let Err(e) = out.validate() {
+			Err(expr_error(expr, search_pos, format!("{e:#}")))?
+		}
+		Ok(out)
+	}
+	/// Check that `search` is a valid regex, it's kept for applying the rule.
+	pub fn validate(&self) -> Result<()> {
+		self.regex().map(|_| ())
+	}
+	/// Byte regex matching `search`, built on first use and again if the fields changed.
+	fn regex(&self) -> Result<Cow<'_, Regex>> {
+		let key = (&self.search, self.regex, self.ignore_case);
+		match self.compiled.0.get() {
+			Some((search, regex, ignore_case, re)) if (search, *regex, *ignore_case) == key => {
+				Ok(Cow::Borrowed(re))
+			}
+			Some(_) => self.compile().map(Cow::Owned),
+			None => {
+				let re = self.compile()?;
+				let (.., re) = self
+					.compiled
+					.0
+					.get_or_init(|| (self.search.clone(), self.regex, self.ignore_case, re));
+				Ok(Cow::Borrowed(re))
+			}
+		}
+	}
+	fn compile(&self) -> Result<Regex> {
+		let pattern = match self.regex {
+			true => self.search.clone(),
+			false => regex::escape(&self.search),
+		};
+		RegexBuilder::new(&pattern)
+			.case_insensitive(self.ignore_case)
+			.build()
+			.with_context(|| format!("Invalid regex \"{}\"", self.search))
+	}
+	/// Apply the rule to the raw bytes of `name`.
+	pub fn apply(&self, name: &mut OsString) -> Result<()> {
+		if self.search.is_empty() {
+			return Ok(());
+		}
+		let re = self.regex()?;
+		*name = map_os_bytes(name, |bytes| {
+			let matches: Vec<_> = re.captures_iter(bytes).collect();
+			let selected = match self.occurrence {
+				Occurrence::All => &matches[..],
+				Occurrence::First => matches.get(..1).unwrap_or_default(),
+				Occurrence::Last => matches
+					.get(matches.len().saturating_sub(1)..)
+					.unwrap_or_default(),
+				Occurrence::Nth(n) => matches.get(n.saturating_sub(1)..n).unwrap_or_default(),
+			};
+			let mut out: Vec<u8> = vec![];
+			let mut last = 0;
+			for caps in selected {
+				let Some(m) = caps.get(0) else { continue };
+				out.extend_from_slice(&bytes[last..m.start()]);
+				match self.regex {
+					true => caps.expand(self.replace.as_bytes(), &mut out),
+					false => out.extend_from_slice(self.replace.as_bytes()),
+				}
+				last = m.end();
+			}
+			out.extend_from_slice(&bytes[last..]);
+			out
+		})?;
+		Ok(())
+	}
+}
+
+impl FromStr for Replace {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self> {
+		Self::parse(s, false)
+	}
+}
+
+fn is_delimiter(c: char) -> bool {
+	c.is_ascii_punctuation() && c != '\\'
+}
+
+/// Error pointing at the char with index `pos` of `expr`.
+fn expr_error(expr: &str, pos: usize, msg: impl std::fmt::Display) -> anyhow::Error {
+	anyhow!(
+		"Invalid replace expression: {msg}\n  {expr}\n  {}^",
+		" ".repeat(pos)
+	)
+}
+
+/// A part of an expression with the positions of escaped chars.
+struct Field {
+	/// (position in expression, char, escaped)
+	chars: Vec<(usize, char, bool)>,
+}
+
+impl Field {
+	/// Resolve escapes. `search` selects the rules for the search or replace part.
+	fn unescape(&self, expr: &str, delim: char, regex: bool, search: bool) -> Result<String> {
+		let mut out = String::new();
+		for &(pos, c, escaped) in &self.chars {
+			match (escaped, c) {
+				(false, c) => out.push(c),
+				(true, c) if c == delim => match regex && search {
+					true => out.push_str(&regex::escape(&c.to_string())),
+					false => out.push(c),
+				},
+				(true, '\\') => match regex && search {
+					true => out.push_str("\\\\"),
+					false => out.push('\\'),
+				},
+				// sed style back reference.
+				(true, d @ '0'..='9') if regex && !search => {
+					out.push_str("${");
+					out.push(d);
+					out.push('}');
+				}
+				(true, c) if regex && search => {
+					out.push('\\');
+					out.push(c);
+				}
+				(true, c) => Err(expr_error(
+					expr,
+					pos.saturating_sub(1),
+					format!("unknown escape '\\{c}', use '\\\\' for a literal backslash"),
+				))?,
+			}
+		}
+		Ok(out)
+	}
+}
+
+/// Split `chars` at unescaped `delim`.
+/// Returns the fields and the position of each delimiter found.
+fn split_fields(
+	expr: &str,
+	chars: &[(usize, char)],
+	delim: char,
+) -> Result<(Vec<Field>, Vec<usize>)> {
+	let mut fields = vec![Field { chars: vec![] }];
+	let mut delims = vec![];
+	let mut iter = chars.iter();
+	while let Some(&(pos, c)) = iter.next() {
+		let field = fields.last_mut().context("No field")?;
+		match c {
+			'\\' => match iter.next() {
+				Some(&(pos, c)) => field.chars.push((pos, c, true)),
+				None => Err(expr_error(expr, pos, "trailing backslash"))?,
+			},
+			c if c == delim => {
+				delims.push(pos);
+				fields.push(Field { chars: vec![] });
+			}
+			c => field.chars.push((pos, c, false)),
+		}
+	}
+	Ok((fields, delims))
+}
+
+fn parse_simple(expr: &str, chars: &[(usize, char)], regex: bool) -> Result<Replace> {
+	let (fields, delims) = split_fields(expr, chars, '/')?;
+	if let Some(&pos) = delims.get(1) {
+		Err(expr_error(
+			expr,
+			pos,
+			"only one '/' allowed, escape it with '\\/' or use s|search|replace|",
+		))?;
+	}
+	let search = fields[0].unescape(expr, '/', regex, true)?;
+	let replace = match fields.get(1) {
+		Some(f) => f.unescape(expr, '/', regex, false)?,
+		None => String::new(),
+	};
+	Ok(Replace {
+		search,
+		replace,
+		regex,
+		..Default::default()
+	})
+}
+
+fn parse_sed(expr: &str, chars: &[(usize, char)], mut regex: bool) -> Result<Replace> {
+	let delim = chars[1].1;
+	let (fields, delims) = split_fields(expr, &chars[2..], delim)?;
+	match delims.len() {
+		0 => Err(expr_error(
+			expr,
+			chars.len(),
+			format!("missing '{delim}' after search term"),
+		))?,
+		1 => Err(expr_error(
+			expr,
+			chars.len(),
+			format!("missing '{delim}' after replacement"),
+		))?,
+		2 => (),
+		_ => Err(expr_error(
+			expr,
+			delims[2],
+			format!("unexpected '{delim}' after flags, escape it with '\\{delim}'"),
+		))?,
+	}
+	if fields[0].chars.is_empty() {
+		Err(expr_error(expr, delims[0], "empty search term"))?;
+	}
+	let mut ignore_case = false;
+	let mut occurrence: Option<(usize, Occurrence)> = None;
+	let mut set_occurrence = |pos: usize, new: Occurrence| match occurrence {
+		Some((prev, _)) => Err(expr_error(
+			expr,
+			pos,
+			format!("conflicts with flag at position {}", prev + 1),
+		)),
+		None => {
+			occurrence = Some((pos, new));
+			Ok(())
+		}
+	};
+	let flags = &fields[2].chars;
+	let mut i = 0;
+	while let Some(&(pos, c, escaped)) = flags.get(i) {
+		i += 1;
+		match (escaped, c) {
+			(false, 'g') => set_occurrence(pos, Occurrence::All)?,
+			(false, 'l') => set_occurrence(pos, Occurrence::Last)?,
+			(false, 'i') if ignore_case => Err(expr_error(expr, pos, "duplicate flag 'i'"))?,
+			(false, 'i') => ignore_case = true,
+			(false, 'r') if regex => Err(expr_error(expr, pos, "duplicate flag 'r'"))?,
+			(false, 'r') => regex = true,
+			(false, '0'..='9') => {
+				let mut n = String::from(c);
+				while let Some(&(_, d @ '0'..='9', false)) = flags.get(i) {
+					n.push(d);
+					i += 1;
+				}
+				match n.parse::<usize>() {
+					Ok(0) | Err(_) => Err(expr_error(
+						expr,
+						pos,
+						format!("invalid occurrence '{n}', expected a number starting at 1"),
+					))?,
+					Ok(n) => set_occurrence(pos, Occurrence::Nth(n))?,
+				}
+			}
+			(_, c) => Err(expr_error(
+				expr,
+				pos,
+				format!("unknown flag '{c}', expected one of g, l, i, r or a number"),
+			))?,
+		}
+	}
+	Ok(Replace {
+		search: fields[0].unescape(expr, delim, regex, true)?,
+		replace: fields[1].unescape(expr, delim, regex, false)?,
+		regex,
+		ignore_case,
+		occurrence: occurrence.map(|(_, o)| o).unwrap_or(Occurrence::First),
+		..Default::default()
+	})
+}
@@ -0,0 +1,208 @@
+use std::{
+	io::Write,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+	args::{LinkKind, OutputFormat},
+	error::ErrorCategory,
+	plan::{Operation, OperationKind},
+};
+
+/// What happened to one operation, printed with `--output-format`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+	#[serde(with = "crate::raw_path")]
+	pub source: PathBuf,
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "crate::raw_path::option"
+	)]
+	pub target: Option<PathBuf>,
+	/// Written like in tsv, as `move`, `link-hard` and so on.
+	#[serde(
+		serialize_with = "serialize_action",
+		deserialize_with = "deserialize_action"
+	)]
+	pub action: OperationKind,
+	pub status: Status,
+	/// Why the operation was skipped or planned like this.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reason: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub category: Option<ErrorCategory>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+	/// Would be done, only with `--dry`.
+	Planned,
+	Done,
+	Skipped,
+	Failed,
+	/// Done, then reverted because of `--undo-on-err`.
+	Undone,
+}
+
+impl Report {
+	pub fn new(op: &Operation, status: Status) -> Self {
+		Self {
+			source: op.source.clone(),
+			target: op.target.clone(),
+			action: op.kind,
+			status,
+			reason: op.reason.as_ref().map(ToString::to_string),
+			category: None,
+			error: None,
+		}
+	}
+	/// Report of an operation which wasn't run because of an earlier error, see `reason`.
+	pub fn not_run(op: &Operation, reason: &str) -> Self {
+		Self {
+			reason: Some(reason.to_string()),
+			..Self::new(op, Status::Skipped)
+		}
+	}
+	pub fn failed(op: &Operation, err: &anyhow::Error) -> Self {
+		Self {
+			category: Some(ErrorCategory::of(err)),
+			error: Some(format!("{err:#}")),
+			..Self::new(op, Status::Failed)
+		}
+	}
+}
+
+/// Write `reports` to `out`.
+///
+/// - `json`: one array.
+/// - `ndjson`: one object per line.
+/// - `tsv`: a header, then `source target action status category error` per line. Tabs,
+///   newlines and backslashes are escaped with a backslash, bytes which aren't valid UTF-8 as
+///   `\xNN`.
+///
+/// In json, names which aren't valid UTF-8 are written as their bytes, see [`crate::raw_path`].
+pub fn write_reports(reports: &[Report], format: OutputFormat, mut out: impl Write) -> Result<()> {
+	match format {
+		OutputFormat::Json => {
+			serde_json::to_writer_pretty(&mut out, reports).context("Failed to write report")?;
+			writeln!(out)?;
+		}
+		OutputFormat::Ndjson => {
+			for report in reports {
+				serde_json::to_writer(&mut out, report).context("Failed to write report")?;
+				writeln!(out)?;
+			}
+		}
+		OutputFormat::Tsv => {
+			writeln!(out, "source\ttarget\taction\tstatus\tcategory\terror")?;
+			for report in reports {
+				writeln!(
+					out,
+					"{}\t{}\t{}\t{}\t{}\t{}",
+					tsv_path(&report.source),
+					report.target.as_deref().map(tsv_path).unwrap_or_default(),
+					action_name(report.action),
+					status_name(report.status),
+					report.category.map(category_name).unwrap_or_default(),
+					escape(report.error.as_deref().unwrap_or_default().as_bytes()),
+				)?;
+			}
+		}
+	}
+	out.flush().context("Failed to write report")
+}
+
+fn tsv_path(path: &Path) -> String {
+	match crate::raw_path::to_bytes(path) {
+		Ok(bytes) => escape(&bytes),
+		Err(_) => escape(path.to_string_lossy().as_bytes()),
+	}
+}
+
+fn escape(bytes: &[u8]) -> String {
+	let mut out = String::with_capacity(bytes.len());
+	for chunk in bytes.utf8_chunks() {
+		for c in chunk.valid().chars() {
+			match c {
+				'\\' => out.push_str("\\\\"),
+				'\t' => out.push_str("\\t"),
+				'\n' => out.push_str("\\n"),
+				'\r' => out.push_str("\\r"),
+				c => out.push(c),
+			}
+		}
+		for b in chunk.invalid() {
+			out.push_str(&format!("\\x{b:02x}"));
+		}
+	}
+	out
+}
+
+const ACTIONS: [OperationKind; 8] = [
+	OperationKind::Move,
+	OperationKind::Copy,
+	OperationKind::Link(LinkKind::Hard),
+	OperationKind::Link(LinkKind::Symbolic),
+	OperationKind::Link(LinkKind::Relative),
+	OperationKind::Swap,
+	OperationKind::Skip,
+	OperationKind::Conflict,
+];
+
+fn action_name(action: OperationKind) -> &'static str {
+	match action {
+		OperationKind::Move => "move",
+		OperationKind::Copy => "copy",
+		OperationKind::Link(LinkKind::Hard) => "link-hard",
+		OperationKind::Link(LinkKind::Symbolic) => "link-symbolic",
+		OperationKind::Link(LinkKind::Relative) => "link-relative",
+		OperationKind::Swap => "swap",
+		OperationKind::Skip => "skip",
+		OperationKind::Conflict => "conflict",
+	}
+}
+
+fn serialize_action<S: Serializer>(
+	action: &OperationKind,
+	serializer: S,
+) -> Result<S::Ok, S::Error> {
+	serializer.serialize_str(action_name(*action))
+}
+
+fn deserialize_action<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<OperationKind, D::Error> {
+	let name = String::deserialize(deserializer)?;
+	ACTIONS
+		.into_iter()
+		.find(|a| action_name(*a) == name)
+		.ok_or_else(|| D::Error::custom(format!("unknown action \"{name}\"")))
+}
+
+fn status_name(status: Status) -> &'static str {
+	match status {
+		Status::Planned => "planned",
+		Status::Done => "done",
+		Status::Skipped => "skipped",
+		Status::Failed => "failed",
+		Status::Undone => "undone",
+	}
+}
+
+fn category_name(category: ErrorCategory) -> &'static str {
+	match category {
+		ErrorCategory::NotFound => "not_found",
+		ErrorCategory::Conflict => "conflict",
+		ErrorCategory::Permission => "permission",
+		ErrorCategory::Io => "io",
+		ErrorCategory::Config => "config",
+		ErrorCategory::Other => "other",
+	}
+}
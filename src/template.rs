@@ -0,0 +1,365 @@
+use std::{
+	ffi::{OsStr, OsString},
+	fmt::Display,
+	path::Path,
+	str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{
+	format::{Item, StrftimeItems},
+	DateTime, Local,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::case::{Case, WordBoundary};
+
+/// Template building the whole new file name.
+///
+/// Text in `{}` is replaced by the value of a placeholder, `{{` and `}}` insert literal braces.
+/// Syntax: `{field[start:end]:format|filter|filter}`, everything except `field` is optional.
+///
+/// Fields:
+/// - `stem`: file name without extension
+/// - `ext`: extension without the leading `.`
+/// - `name`: file name with extension
+/// - `parent`: name of the directory containing the file
+/// - `n`: counter, format is the minimum amount of digits (`{n:3}` -> `001`)
+/// - `mtime`: modification time, format is a strftime string (default `%Y-%m-%d`)
+/// - `size`: size in bytes
+///
+/// Slices count chars and can be negative to count from the end (`{stem[-3:]}`).
+/// Filters: `trim` and the case conversions `lower`, `upper`, `title`, `sentence`, `snake`,
+/// `kebab`, `camel` and `pascal`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Template {
+	source: String,
+	segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+	Text(String),
+	Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placeholder {
+	field: Field,
+	slice: Option<(Option<isize>, Option<isize>)>,
+	filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+	Stem,
+	Ext,
+	Name,
+	Parent,
+	N { width: Option<usize> },
+	Mtime { format: String },
+	Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+	Case(Case),
+	Trim,
+}
+
+/// Values available while rendering a [`Template`].
+pub struct TemplateContext<'a> {
+	/// Original path of the file.
+	pub path: &'a Path,
+	/// Current name of the file, used for `{stem}`, `{ext}` and `{name}`.
+	pub name: &'a OsStr,
+	/// Value of `{n}`.
+	pub n: u64,
+	/// Minimum amount of digits of `{n}` if the template doesn't set one.
+	pub pad: usize,
+}
+
+impl Template {
+	pub fn parse(source: &str) -> Result<Self> {
+		let mut segments = vec![];
+		let mut text = String::new();
+		let mut chars = source.char_indices().peekable();
+		while let Some((pos, c)) = chars.next() {
+			match c {
+				'{' if chars.next_if(|(_, c)| *c == '{').is_some() => text.push('{'),
+				'}' if chars.next_if(|(_, c)| *c == '}').is_some() => text.push('}'),
+				'{' => {
+					let end = source[pos..]
+						.find('}')
+						.map(|e| e + pos)
+						.ok_or_else(|| template_error(source, pos, "unclosed '{'"))?;
+					if !text.is_empty() {
+						segments.push(Segment::Text(std::mem::take(&mut text)));
+					}
+					segments.push(Segment::Placeholder(Placeholder::parse(
+						source,
+						pos + 1,
+						&source[pos + 1..end],
+					)?));
+					while chars.next_if(|(p, _)| *p <= end).is_some() {}
+				}
+				'}' => Err(template_error(source, pos, "unmatched '}', use '}}'"))?,
+				c => text.push(c),
+			}
+		}
+		if !text.is_empty() {
+			segments.push(Segment::Text(text));
+		}
+		Ok(Self {
+			source: source.to_string(),
+			segments,
+		})
+	}
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+	/// Whether the template contains a `{n}` placeholder.
+	pub fn uses_counter(&self) -> bool {
+		self.segments.iter().any(|s| {
+			matches!(
+				s,
+				Segment::Placeholder(Placeholder {
+					field: Field::N { .. },
+					..
+				})
+			)
+		})
+	}
+	/// Build the new file name.
+	pub fn render(&self, ctx: &TemplateContext) -> Result<OsString> {
+		let mut out = OsString::new();
+		for segment in &self.segments {
+			match segment {
+				Segment::Text(t) => out.push(t),
+				Segment::Placeholder(p) => out.push(p.render(ctx)?),
+			}
+		}
+		// Rejects `.`, `..` and names with a separator, like `a/.` which is one component too.
+		if Path::new(&out).file_name() != Some(out.as_os_str()) {
+			Err(anyhow!(
+				"Template \"{}\" results in invalid file name {:?} for {}",
+				self.source,
+				out,
+				ctx.path.display()
+			))?;
+		}
+		Ok(out)
+	}
+}
+
+impl Placeholder {
+	/// Parse the content of `{}` starting at byte `offset` of `source`.
+	fn parse(source: &str, offset: usize, inner: &str) -> Result<Self> {
+		let mut filters = inner.split('|');
+		let head = filters.next().unwrap_or_default();
+		let (head, spec) = {
+			// ':' inside of the slice doesn't start the format.
+			let search_from = head.find(']').unwrap_or(0);
+			match head[search_from..].find(':') {
+				Some(i) => (&head[..search_from + i], Some(&head[search_from + i + 1..])),
+				None => (head, None),
+			}
+		};
+		let (name, slice) = match head.find('[') {
+			Some(open) => {
+				let slice = head[open + 1..].strip_suffix(']').ok_or_else(|| {
+					template_error(source, offset + open, "expected ']' after slice")
+				})?;
+				(
+					&head[..open],
+					Some(parse_slice(slice).ok_or_else(|| {
+						template_error(
+							source,
+							offset + open + 1,
+							format!("invalid slice \"{slice}\", expected [start:end]"),
+						)
+					})?),
+				)
+			}
+			None => (head, None),
+		};
+		let spec_pos = offset + head.len() + 1;
+		let field = match (name.trim(), spec) {
+			("stem", None) => Field::Stem,
+			("ext", None) => Field::Ext,
+			("name", None) => Field::Name,
+			("parent", None) => Field::Parent,
+			("size", None) => Field::Size,
+			("n", None) => Field::N { width: None },
+			("n", Some(w)) => Field::N {
+				width: Some(w.parse().map_err(|_| {
+					template_error(
+						source,
+						spec_pos,
+						format!("invalid width \"{w}\", expected a number"),
+					)
+				})?),
+			},
+			("mtime", format) => {
+				let format = format.unwrap_or("%Y-%m-%d");
+				if StrftimeItems::new(format).any(|i| i == Item::Error) {
+					Err(template_error(
+						source,
+						spec_pos,
+						format!("invalid time format \"{format}\""),
+					))?;
+				}
+				Field::Mtime {
+					format: format.to_string(),
+				}
+			}
+			("stem" | "ext" | "name" | "parent" | "size", Some(_)) => Err(template_error(
+				source,
+				spec_pos,
+				format!("field \"{name}\" takes no format"),
+			))?,
+			(name, _) => Err(template_error(
+				source,
+				offset,
+				format!(
+					"unknown field \"{name}\", expected stem, ext, name, parent, n, mtime or size"
+				),
+			))?,
+		};
+		let filters = filters
+			.map(|f| match f.trim() {
+				"trim" => Ok(Filter::Trim),
+				f => match Case::from_str(f, true) {
+					Ok(case) => Ok(Filter::Case(case)),
+					Err(_) => Err(template_error(
+						source,
+						offset + inner.find(f).unwrap_or_default(),
+						format!(
+							"unknown filter \"{f}\", expected trim or a case like lower or snake"
+						),
+					)),
+				},
+			})
+			.collect::<Result<_>>()?;
+		Ok(Self {
+			field,
+			slice,
+			filters,
+		})
+	}
+	fn render(&self, ctx: &TemplateContext) -> Result<OsString> {
+		let path = ctx.path;
+		let name = Path::new(ctx.name);
+		let value: OsString = match &self.field {
+			Field::Stem => name.file_stem().unwrap_or_default().to_owned(),
+			Field::Ext => name.extension().unwrap_or_default().to_owned(),
+			Field::Name => ctx.name.to_owned(),
+			Field::Parent => path
+				.canonicalize()
+				.with_context(|| format!("Failed to resolve {}", path.display()))?
+				.parent()
+				.and_then(Path::file_name)
+				.unwrap_or_default()
+				.to_owned(),
+			Field::N { width } => {
+				format!("{:0width$}", ctx.n, width = width.unwrap_or(ctx.pad)).into()
+			}
+			Field::Mtime { format } => {
+				let mtime = path
+					.metadata()
+					.and_then(|m| m.modified())
+					.with_context(|| format!("Failed to get mtime of {}", path.display()))?;
+				DateTime::<Local>::from(mtime)
+					.format_with_items(StrftimeItems::new(format))
+					.to_string()
+					.into()
+			}
+			Field::Size => path
+				.metadata()
+				.with_context(|| format!("Failed to get size of {}", path.display()))?
+				.len()
+				.to_string()
+				.into(),
+		};
+		if self.slice.is_none() && self.filters.is_empty() {
+			return Ok(value);
+		}
+		let mut value: String = value
+			.into_string()
+			.map_err(|v| anyhow!("{v:?} is not valid unicode, can't use slices or filters"))?;
+		if let Some((start, end)) = self.slice {
+			value = slice_chars(&value, start, end);
+		}
+		for filter in &self.filters {
+			value = match filter {
+				Filter::Case(case) => case.convert(&value, WordBoundary::default()),
+				Filter::Trim => value.trim().to_string(),
+			}
+		}
+		Ok(value.into())
+	}
+}
+
+fn parse_slice(slice: &str) -> Option<(Option<isize>, Option<isize>)> {
+	let (start, end) = slice.split_once(':')?;
+	let parse = |s: &str| match s.trim() {
+		"" => Some(None),
+		s => s.parse().ok().map(Some),
+	};
+	Some((parse(start)?, parse(end)?))
+}
+
+/// Python like slice of chars.
+fn slice_chars(str: &str, start: Option<isize>, end: Option<isize>) -> String {
+	let len = str.chars().count() as isize;
+	let resolve = |i: isize| match i < 0 {
+		true => (len + i).max(0),
+		false => i.min(len),
+	} as usize;
+	let start = start.map(resolve).unwrap_or(0);
+	let end = end.map(resolve).unwrap_or(len as usize);
+	str.chars()
+		.skip(start)
+		.take(end.saturating_sub(start))
+		.collect()
+}
+
+/// Error pointing at byte `pos` of `source`.
+fn template_error(source: &str, pos: usize, msg: impl Display) -> anyhow::Error {
+	let col = source
+		.get(..pos)
+		.map(|s| s.chars().count())
+		.unwrap_or_default();
+	anyhow!(
+		"Invalid template: {msg}\n  {source}\n  {}^",
+		" ".repeat(col)
+	)
+}
+
+impl FromStr for Template {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self> {
+		Self::parse(s)
+	}
+}
+
+impl TryFrom<String> for Template {
+	type Error = anyhow::Error;
+	fn try_from(value: String) -> Result<Self> {
+		Self::parse(&value)
+	}
+}
+
+impl From<Template> for String {
+	fn from(value: Template) -> Self {
+		value.source
+	}
+}
+
+impl Display for Template {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.source)
+	}
+}
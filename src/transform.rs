@@ -0,0 +1,144 @@
+use std::{
+	ffi::OsString,
+	fmt::Debug,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	case::CaseConversion,
+	config::Config,
+	error::Error,
+	replace::Replace,
+	template::{Template, TemplateContext},
+};
+
+/// New name of a file while it goes through the steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileName {
+	pub stem: OsString,
+	/// Extension including the leading `.`, empty if there is none.
+	pub ext: OsString,
+}
+
+/// Information about the file being renamed.
+pub struct TransformContext<'a> {
+	/// Original path of the file.
+	pub path: &'a Path,
+	/// Value of the counter.
+	pub n: u64,
+	/// Minimum amount of digits of the counter.
+	pub pad: usize,
+	pub config: &'a Config,
+}
+
+/// A step changing the name of a file.
+///
+/// Implement this to add custom steps with [`Step::custom`].
+pub trait Transform: Debug + Send + Sync {
+	fn apply(&self, name: &mut FileName, ctx: &TransformContext) -> Result<()>;
+	/// Whether the step needs the counter.
+	fn uses_counter(&self) -> bool {
+		false
+	}
+}
+
+/// Built-in steps, applied in order by [`crate::Rename`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Step {
+	Prefix {
+		text: String,
+	},
+	Suffix {
+		text: String,
+	},
+	Replace(Replace),
+	/// Replacements of a set from the config.
+	Set {
+		name: String,
+	},
+	Case(CaseConversion),
+	/// Replace the whole name.
+	Template {
+		template: Template,
+	},
+	#[serde(skip)]
+	Custom(Arc<dyn Transform>),
+}
+
+impl FileName {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		Some(Self {
+			stem: path.file_stem().or(path.file_name())?.to_owned(),
+			ext: path
+				.extension()
+				.map(|e| {
+					let mut out = OsString::from(".");
+					out.push(e);
+					out
+				})
+				.unwrap_or_default(),
+		})
+	}
+	pub fn joined(&self) -> OsString {
+		let mut out = self.stem.clone();
+		out.push(&self.ext);
+		out
+	}
+}
+
+impl Step {
+	pub fn custom(transform: impl Transform + 'static) -> Self {
+		Self::Custom(Arc::new(transform))
+	}
+}
+
+impl Transform for Step {
+	fn apply(&self, name: &mut FileName, ctx: &TransformContext) -> Result<()> {
+		match self {
+			Self::Prefix { text } => {
+				let mut out = OsString::from(text);
+				out.push(&name.stem);
+				name.stem = out;
+			}
+			Self::Suffix { text } => name.stem.push(text),
+			Self::Replace(r) => r.apply(&mut name.stem)?,
+			Self::Set { name: set_name } => {
+				let set = &ctx
+					.config
+					.sets
+					.get(set_name)
+					.ok_or_else(|| Error::SetNotFound {
+						name: set_name.clone(),
+					})?;
+				for replace in set.set.replacements() {
+					replace.apply(&mut name.stem)?
+				}
+			}
+			Self::Case(c) => c.apply(&mut name.stem, &mut name.ext)?,
+			Self::Template { template } => {
+				let rendered = template.render(&TemplateContext {
+					path: ctx.path,
+					name: &name.joined(),
+					n: ctx.n,
+					pad: ctx.pad,
+				})?;
+				*name = FileName::from_path(&PathBuf::from(rendered))
+					.context("Template result is empty")?;
+			}
+			Self::Custom(t) => t.apply(name, ctx)?,
+		}
+		Ok(())
+	}
+	fn uses_counter(&self) -> bool {
+		match self {
+			Self::Template { template } => template.uses_counter(),
+			Self::Custom(t) => t.uses_counter(),
+			_ => false,
+		}
+	}
+}
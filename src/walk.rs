@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ignore::{
+	overrides::{Override, OverrideBuilder},
+	WalkBuilder,
+};
+
+/// Which files to collect when walking directories with `--recursive`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Walk {
+	/// Only files matching one of these globs, all if empty.
+	pub include: Vec<String>,
+	/// Skip files and directories matching one of these globs.
+	pub exclude: Vec<String>,
+	/// How deep to go below the given directories, unlimited if `None`.
+	pub max_depth: Option<usize>,
+	/// Also collect hidden files.
+	pub hidden: bool,
+	/// Skip files ignored by `.gitignore` and `.ignore` files.
+	pub gitignore: bool,
+}
+
+impl Walk {
+	/// Files below `roots`, with directories after their contents if `dirs` is set.
+	///
+	/// Roots which aren't directories are passed through.
+	pub fn files(&self, roots: &[PathBuf], dirs: bool) -> Result<Vec<PathBuf>> {
+		let mut out = vec![];
+		for root in roots {
+			match root.is_dir() {
+				true => out.extend(self.walk(root, dirs)?),
+				false => out.push(root.clone()),
+			}
+		}
+		Ok(out)
+	}
+	fn walk(&self, root: &Path, dirs: bool) -> Result<Vec<PathBuf>> {
+		// Whitelisted globs would also override hidden and ignored files, so `include` is
+		// checked separately.
+		let include = globs(root, self.include.iter().cloned())?;
+		let exclude = globs(root, self.exclude.iter().map(|g| format!("!{g}")))?;
+		let walker = WalkBuilder::new(root)
+			.max_depth(self.max_depth)
+			.hidden(!self.hidden)
+			.git_ignore(self.gitignore)
+			.git_global(self.gitignore)
+			.git_exclude(self.gitignore)
+			.ignore(self.gitignore)
+			.parents(self.gitignore)
+			.overrides(exclude)
+			.sort_by_file_name(|a, b| a.cmp(b))
+			.build();
+		let mut out = vec![];
+		// Directories waiting for their contents, with their depth.
+		let mut pending: Vec<(usize, PathBuf)> = vec![];
+		for entry in walker {
+			let entry = entry.with_context(|| format!("Failed to walk {}", root.display()))?;
+			while matches!(pending.last(), Some((depth, _)) if *depth >= entry.depth()) {
+				out.extend(pending.pop().map(|(_, dir)| dir));
+			}
+			let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+			// Directories are always walked, `include` only decides if they are renamed.
+			if !self.include.is_empty() && !include.matched(entry.path(), is_dir).is_whitelist() {
+				continue;
+			}
+			match is_dir {
+				true if dirs => pending.push((entry.depth(), entry.into_path())),
+				true => (),
+				false => out.push(entry.into_path()),
+			}
+		}
+		out.extend(pending.into_iter().rev().map(|(_, dir)| dir));
+		Ok(out)
+	}
+}
+
+fn globs(root: &Path, globs: impl Iterator<Item = String>) -> Result<Override> {
+	let mut out = OverrideBuilder::new(root);
+	for glob in globs {
+		out.add(&glob)
+			.with_context(|| format!("Invalid glob {glob:?}"))?;
+	}
+	out.build().context("Failed to build globs")
+}
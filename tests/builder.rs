@@ -0,0 +1,39 @@
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+use renameplus::{Rename, Replace, Step};
+
+#[test]
+fn build() {
+	let dir = TestDir::new("builder");
+	let file = dir.join("IMG_0001.jpg");
+	fs::write(&file, "").expect("Failed to create test file");
+	let rename = Rename::builder()
+		.file(&file)
+		.replace(Replace::new("IMG_", "holiday_"))
+		.suffix("_edited")
+		.dry(true)
+		.build()
+		.expect("Failed to build");
+	assert_eq!(
+		rename.get_new_path(&file).expect("Failed to get new path"),
+		Some(dir.join("holiday_0001_edited.jpg"))
+	);
+	assert!(matches!(rename.steps[1], Step::Suffix { .. }));
+}
+
+#[test]
+fn invalid() {
+	assert!(Rename::builder().set("missing").build().is_err());
+	assert!(Rename::builder().pipeline("missing").build().is_err());
+	assert!(Rename::builder()
+		.replace(Replace::new_regex("(", ""))
+		.build()
+		.is_err());
+	assert!(Rename::builder()
+		.output_dir("/does/not/exist")
+		.build()
+		.is_err());
+}
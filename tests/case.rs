@@ -0,0 +1,78 @@
+use std::ffi::OsString;
+
+use renameplus::{Case, CaseConversion, CaseTarget, WordBoundary};
+
+fn convert(case: Case, str: &str, boundary: WordBoundary) -> String {
+	case.convert(str, boundary)
+}
+
+#[test]
+fn cases() {
+	let sep = WordBoundary::Separator;
+	assert_eq!(convert(Case::Lower, "ÄPFEL Straße", sep), "äpfel straße");
+	assert_eq!(convert(Case::Upper, "straße", sep), "STRASSE");
+	assert_eq!(
+		convert(Case::Title, "my_holiday photo", sep),
+		"My_Holiday Photo"
+	);
+	assert_eq!(convert(Case::Sentence, "MY HOLIDAY", sep), "My holiday");
+	assert_eq!(
+		convert(Case::Snake, "My Holiday-Photo", sep),
+		"my_holiday_photo"
+	);
+	assert_eq!(
+		convert(Case::Kebab, "My Holiday_Photo", sep),
+		"my-holiday-photo"
+	);
+	assert_eq!(
+		convert(Case::Camel, "my holiday photo", sep),
+		"myHolidayPhoto"
+	);
+	assert_eq!(
+		convert(Case::Pascal, "my holiday photo", sep),
+		"MyHolidayPhoto"
+	);
+}
+
+#[test]
+fn titlecase() {
+	let sep = WordBoundary::Separator;
+	assert_eq!(convert(Case::Title, "ǆungla ǉubav", sep), "ǅungla ǈubav");
+	assert_eq!(convert(Case::Pascal, "Ǌive ǲem", sep), "ǋiveǲem");
+	assert_eq!(convert(Case::Sentence, "ßig ﬁle", sep), "Ssig ﬁle");
+	assert_eq!(
+		convert(Case::Title, "ﬁle ᾳδω ᾷ", sep),
+		"File ᾼδω Α\u{342}\u{345}"
+	);
+}
+
+#[test]
+fn boundaries() {
+	assert_eq!(
+		convert(Case::Snake, "parseHTTPServer2", WordBoundary::All),
+		"parse_http_server_2"
+	);
+	assert_eq!(
+		convert(Case::Snake, "parseHTTP server", WordBoundary::Separator),
+		"parsehttp_server"
+	);
+	assert_eq!(convert(Case::Snake, "a_b c", WordBoundary::Space), "a_b_c");
+}
+
+#[test]
+fn targets() {
+	let apply = |target| {
+		let (mut stem, mut ext) = (OsString::from("My File"), OsString::from(".TXT"));
+		CaseConversion {
+			case: Case::Snake,
+			target,
+			boundary: WordBoundary::Separator,
+		}
+		.apply(&mut stem, &mut ext)
+		.expect("Failed to convert");
+		(stem, ext)
+	};
+	assert_eq!(apply(CaseTarget::Stem), ("my_file".into(), ".TXT".into()));
+	assert_eq!(apply(CaseTarget::Ext), ("My File".into(), ".txt".into()));
+	assert_eq!(apply(CaseTarget::Name), ("my_file".into(), ".txt".into()));
+}
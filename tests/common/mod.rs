@@ -0,0 +1,45 @@
+use std::{
+	fs,
+	ops::Deref,
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Empty directory for one test, removed when dropped, also if the test panics.
+///
+/// The name is unique per process and call, so tests can run in parallel and in several
+/// `cargo test` at once.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+	pub fn new(name: &str) -> Self {
+		static COUNT: AtomicUsize = AtomicUsize::new(0);
+		let dir = std::env::temp_dir().join(format!(
+			"renameplus_test_{name}_{}_{}",
+			std::process::id(),
+			COUNT.fetch_add(1, Ordering::Relaxed)
+		));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).expect("Failed to create test dir");
+		Self(dir)
+	}
+}
+
+impl Deref for TestDir {
+	type Target = PathBuf;
+	fn deref(&self) -> &PathBuf {
+		&self.0
+	}
+}
+
+impl AsRef<Path> for TestDir {
+	fn as_ref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for TestDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.0);
+	}
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::TestDir;
+use renameplus::{
+	fs::{remove_path, rename_noreplace},
+	Error, ErrorCategory, Rename,
+};
+
+#[test]
+fn categories() {
+	let err = Rename::builder()
+		.set("missing")
+		.build()
+		.expect_err("Set should be missing");
+	assert!(matches!(Error::find(&err), Some(Error::SetNotFound { name }) if name == "missing"));
+	assert_eq!(ErrorCategory::of(&err), ErrorCategory::Config);
+
+	let dir = TestDir::new("missing");
+	let file = dir.join("missing");
+	let err = Rename::builder()
+		.file(&file)
+		.prefix("a")
+		.build()
+		.expect("Failed to build")
+		.get_new_path(&file)
+		.expect_err("File should be missing");
+	let err = Error::find(&err).expect("Not a renameplus error");
+	assert_eq!(err.category(), ErrorCategory::NotFound);
+	assert_eq!(err.path(), Some(&file));
+	// Only a missing source of an operation vanished.
+	assert!(matches!(remove_path(&file), Err(Error::NotFound { .. })));
+	assert!(matches!(
+		rename_noreplace(&file, &dir.join("other")),
+		Err(Error::SourceVanished { path }) if path == file
+	));
+
+	let err = anyhow::anyhow!("other");
+	assert_eq!(ErrorCategory::of(&err), ErrorCategory::Other);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::{cell::Cell, fs, rc::Rc};
+
+use common::TestDir;
+use renameplus::{
+	fs::{copy_noreplace, move_across, rename_noreplace, swap, CopyOptions},
+	Error, Rename,
+};
+
+#[test]
+fn noreplace() {
+	let dir = TestDir::new("noreplace");
+	let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+	fs::write(&a, "a").expect("Failed to create test file");
+	fs::write(&b, "b").expect("Failed to create test file");
+	let err = rename_noreplace(&a, &b).expect_err("Target replaced");
+	assert!(matches!(err, Error::TargetExists { path } if path == b));
+	let err = copy_noreplace(&a, &b).expect_err("Target replaced");
+	assert!(matches!(err, Error::TargetExists { path } if path == b));
+	assert_eq!(fs::read_to_string(&b).expect("Missing"), "b");
+	// Missing directory of the target, the source is still there.
+	let err = rename_noreplace(&a, &dir.join("missing").join("a")).expect_err("Renamed");
+	assert!(matches!(err, Error::NotFound { path } if path == dir.join("missing")));
+	rename_noreplace(&a, &c).expect("Failed to rename");
+	assert!(!a.exists());
+	assert_eq!(fs::read_to_string(&c).expect("Missing"), "a");
+}
+
+#[test]
+fn target_appears_after_planning() {
+	let dir = TestDir::new("appears");
+	fs::write(dir.join("a"), "a").expect("Failed to create test file");
+	let plan = Rename::builder()
+		.file(dir.join("a"))
+		.suffix("_new")
+		.build()
+		.expect("Failed to build")
+		.plan()
+		.expect("Failed to plan");
+	fs::write(dir.join("a_new"), "other").expect("Failed to create test file");
+	// Skipped by the default conflict policy, without touching the new file.
+	let log = plan.execute_with(false, |_| unreachable!());
+	assert!(log.entries().is_empty());
+	assert_eq!(
+		fs::read_to_string(dir.join("a_new")).expect("Missing"),
+		"other"
+	);
+	assert!(dir.join("a").exists());
+}
+
+#[test]
+fn swap_files() {
+	let dir = TestDir::new("swap");
+	fs::create_dir_all(dir.join("b")).expect("Failed to create test dir");
+	let (a, b) = (dir.join("a"), dir.join("b"));
+	fs::write(&a, "a").expect("Failed to create test file");
+	assert!(Rename::builder()
+		.dry(true)
+		.build()
+		.expect("Failed to build")
+		.swap(&a, &b)
+		.entries()
+		.is_empty());
+	assert!(a.is_file());
+	swap(&a, &b).expect("Failed to swap");
+	assert!(a.is_dir());
+	assert_eq!(fs::read_to_string(&b).expect("Missing"), "a");
+	let err = Rename::default()
+		.plan_swap(&a, &dir.join("c"))
+		.expect_err("Swapped with missing file");
+	assert!(matches!(
+		renameplus::Error::find(&err),
+		Some(renameplus::Error::NotFound { .. })
+	));
+}
+
+#[test]
+fn move_across_filesystems() {
+	let dir = TestDir::new("move_across");
+	fs::create_dir_all(dir.join("tree").join("sub")).expect("Failed to create test dir");
+	fs::write(dir.join("file"), "file").expect("Failed to create test file");
+	fs::write(dir.join("tree").join("sub").join("a"), "a").expect("Failed to create test file");
+	// The fallback is run directly, it works on the same filesystem too.
+	let other = dir.join("other");
+	fs::create_dir_all(&other).expect("Failed to create test dir");
+	let copied = Rc::new(Cell::new(0));
+	let mut opts = CopyOptions::default().progress({
+		let copied = copied.clone();
+		move |_, n, _| copied.set(n)
+	});
+	move_across(&dir.join("file"), &other.join("file"), &mut opts).expect("Failed to move file");
+	assert_eq!(copied.get(), 4);
+	move_across(&dir.join("tree"), &other.join("tree"), &mut opts).expect("Failed to move tree");
+	assert!(!dir.join("file").exists());
+	assert!(!dir.join("tree").exists());
+	assert_eq!(
+		fs::read_to_string(other.join("tree").join("sub").join("a")).expect("Missing"),
+		"a"
+	);
+	// Target is never replaced, and nothing is left behind.
+	fs::write(dir.join("file"), "new").expect("Failed to create test file");
+	let err = move_across(&dir.join("file"), &other.join("file"), &mut opts)
+		.expect_err("Target replaced");
+	assert!(matches!(err, Error::TargetExists { .. }));
+	assert_eq!(fs::read_dir(&other).expect("Failed to read dir").count(), 2);
+}
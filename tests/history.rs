@@ -0,0 +1,149 @@
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+use renameplus::{history::History, Error, OperationKind, Rename};
+
+#[derive(Debug)]
+struct Swap;
+
+impl renameplus::Transform for Swap {
+	fn apply(
+		&self,
+		name: &mut renameplus::FileName,
+		_: &renameplus::TransformContext,
+	) -> anyhow::Result<()> {
+		name.stem = match name.stem.to_str() {
+			Some("c") => "d",
+			_ => "c",
+		}
+		.into();
+		Ok(())
+	}
+}
+
+#[test]
+fn record_and_undo() {
+	let dir = TestDir::new("history");
+	for name in ["a", "b"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let rename = |file: &str| {
+		Rename::builder()
+			.file(dir.join(file))
+			.suffix("_new")
+			.history(dir.join("history"))
+			.build()
+			.expect("Failed to build")
+			.rename()
+	};
+	assert!(rename("a").entries().is_empty());
+	assert!(rename("b").entries().is_empty());
+	let history = History::new(dir.join("history"));
+	let batches = history.batches().expect("Failed to read history");
+	assert_eq!(batches.iter().map(|b| b.id).collect::<Vec<_>>(), [1, 2]);
+	assert_eq!(batches[0].operations[0].target, dir.join("a_new"));
+
+	// Target changed, nothing is touched.
+	fs::write(dir.join("b_new"), "changed").expect("Failed to change test file");
+	let err = history
+		.last()
+		.expect("Failed to read history")
+		.expect("No batch")
+		.verify()
+		.expect_err("Change not detected");
+	assert!(matches!(
+		Error::find(&err),
+		Some(Error::TargetChanged { .. })
+	));
+	assert!(!history.undo(None, false).entries().is_empty());
+	assert!(dir.join("b_new").exists());
+
+	assert!(history.undo(Some(1), false).entries().is_empty());
+	assert!(dir.join("a").exists());
+	assert!(!dir.join("a_new").exists());
+	assert!(history.get(1).expect("Batch missing").undone.is_some());
+	assert!(!history.undo(Some(1), false).entries().is_empty());
+
+	// Cycle, through a temporary name.
+	fs::write(dir.join("c"), "c").expect("Failed to create test file");
+	fs::write(dir.join("d"), "d").expect("Failed to create test file");
+	assert!(Rename::builder()
+		.files([dir.join("c"), dir.join("d")])
+		.transform(Swap)
+		.history(dir.join("history"))
+		.build()
+		.expect("Failed to build")
+		.rename()
+		.entries()
+		.is_empty());
+	assert_eq!(fs::read_to_string(dir.join("c")).expect("Missing"), "d");
+	assert!(history.undo(None, false).entries().is_empty());
+	assert_eq!(fs::read_to_string(dir.join("c")).expect("Missing"), "c");
+	assert_eq!(fs::read_to_string(dir.join("d")).expect("Missing"), "d");
+}
+
+#[test]
+fn swap_is_recorded() {
+	let dir = TestDir::new("history_swap");
+	let (a, b) = (dir.join("a"), dir.join("b"));
+	fs::write(&a, "a").expect("Failed to create test file");
+	fs::write(&b, "b").expect("Failed to create test file");
+	assert!(Rename::builder()
+		.history(dir.join("history"))
+		.build()
+		.expect("Failed to build")
+		.swap(&a, &b)
+		.entries()
+		.is_empty());
+	assert_eq!(fs::read_to_string(&a).expect("Missing"), "b");
+	let history = History::new(dir.join("history"));
+	let batch = history.get(1).expect("Batch missing");
+	assert_eq!(batch.operations.len(), 1);
+	assert_eq!(batch.operations[0].kind, OperationKind::Swap);
+	assert!(history.undo(None, false).entries().is_empty());
+	assert_eq!(fs::read_to_string(&a).expect("Missing"), "a");
+	assert_eq!(fs::read_to_string(&b).expect("Missing"), "b");
+}
+
+#[test]
+fn partial_undo() {
+	let dir = TestDir::new("history_partial");
+	for name in ["a", "b"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	assert!(Rename::builder()
+		.files([dir.join("a"), dir.join("b")])
+		.suffix("_new")
+		.history(dir.join("history"))
+		.build()
+		.expect("Failed to build")
+		.rename()
+		.entries()
+		.is_empty());
+	let history = History::new(dir.join("history"));
+
+	// a_new can't go back, b_new does.
+	fs::write(dir.join("a"), "other").expect("Failed to create test file");
+	assert!(!history.undo(None, false).entries().is_empty());
+	assert!(dir.join("b").exists());
+	let batch = history.get(1).expect("Batch missing");
+	assert!(batch.partially_undone());
+	assert_eq!(
+		batch
+			.operations
+			.iter()
+			.map(|d| d.undone)
+			.collect::<Vec<_>>(),
+		[false, true]
+	);
+
+	// Only the rest is undone, and a corrupt batch doesn't get in the way.
+	fs::remove_file(dir.join("a")).expect("Failed to remove test file");
+	fs::write(dir.join("history").join("2.json"), "{").expect("Failed to write");
+	assert_eq!(history.batches().expect("Failed to read history").len(), 1);
+	assert!(history.undo(None, false).entries().is_empty());
+	assert_eq!(fs::read_to_string(dir.join("a")).expect("Missing"), "a");
+	assert!(history.get(1).expect("Batch missing").undone.is_some());
+}
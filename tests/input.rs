@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use renameplus::parse_paths;
+
+#[test]
+fn paths() {
+	assert_eq!(
+		parse_paths(b"a.txt\nsub dir/b.txt\n\n", false).expect("Failed to parse"),
+		["a.txt", "sub dir/b.txt"].map(PathBuf::from)
+	);
+	assert_eq!(
+		parse_paths(b"line\nbreak.txt\0c.txt\0", true).expect("Failed to parse"),
+		["line\nbreak.txt", "c.txt"].map(PathBuf::from)
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8() {
+	use renameplus::{Operation, OperationKind, RenamePlan};
+	use std::os::unix::ffi::OsStrExt;
+	let paths = parse_paths(b"caf\xe9.txt\0", true).expect("Failed to parse");
+	assert_eq!(paths[0].as_os_str().as_bytes(), b"caf\xe9.txt");
+
+	// Plans keep the name.
+	let plan = RenamePlan {
+		operations: vec![Operation {
+			kind: OperationKind::Move,
+			source: paths[0].clone(),
+			target: Some(PathBuf::from("cafe.txt")),
+			reason: None,
+			fingerprint: None,
+		}],
+		..Default::default()
+	};
+	let json = plan.to_json().expect("Failed to serialize");
+	assert_eq!(RenamePlan::from_json(&json).expect("Failed to parse"), plan);
+	let toml = plan.to_toml().expect("Failed to serialize");
+	assert_eq!(RenamePlan::from_toml(&toml).expect("Failed to parse"), plan);
+}
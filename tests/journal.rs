@@ -0,0 +1,154 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::TestDir;
+use renameplus::{
+	history::History,
+	journal::{Journal, Unfinished},
+	Rename, RenamePlan,
+};
+
+/// Journal of a run killed after the first of two moves.
+fn interrupted(dir: &Path) -> RenamePlan {
+	fs::create_dir_all(dir.join("journal")).expect("Failed to create test dir");
+	for name in ["a", "b"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let plan = Rename::builder()
+		.files([dir.join("a"), dir.join("b")])
+		.suffix("_new")
+		.build()
+		.expect("Failed to build")
+		.plan()
+		.expect("Failed to plan");
+	fs::rename(dir.join("a"), dir.join("a_new")).expect("Failed to rename");
+	let plan_json: serde_json::Value =
+		serde_json::from_str(&plan.to_json().expect("Failed to serialize")).expect("Invalid json");
+	let op = |i: usize| serde_json::to_value(&plan.operations[i]).expect("Failed to serialize");
+	let lines = [
+		serde_json::json!({"event": "begin", "pid": 0, "plan": plan_json}),
+		serde_json::json!({"event": "started", "index": 0, "operation": op(0)}),
+		serde_json::json!({"event": "completed", "index": 0}),
+		serde_json::json!({"event": "started", "index": 1, "operation": op(1)}),
+	]
+	.map(|l| l.to_string());
+	// Cut off in the middle of a line.
+	let journal = lines.join("\n") + "\n{\"event\": \"compl";
+	fs::write(dir.join("journal").join("1.ndjson"), journal).expect("Failed to write journal");
+	plan
+}
+
+#[test]
+fn finish() {
+	let dir = TestDir::new("journal_finish");
+	interrupted(&dir);
+	let mut found = Unfinished::find(&dir.join("journal")).expect("Failed to read journals");
+	assert_eq!(found.len(), 1);
+	assert_eq!(found[0].started.len(), 2);
+	assert!(found
+		.remove(0)
+		.finish(Some(&dir.join("history")))
+		.entries()
+		.is_empty());
+	assert!(dir.join("a_new").exists());
+	assert!(dir.join("b_new").exists());
+	assert!(Unfinished::find(&dir.join("journal"))
+		.expect("Failed to read journals")
+		.is_empty());
+	// Recorded with what was done before the interruption.
+	let history = History::new(dir.join("history"));
+	let batch = history.get(1).expect("Batch missing");
+	assert_eq!(batch.operations.len(), 2);
+	assert!(history.undo(None, false).entries().is_empty());
+	assert!(dir.join("a").exists());
+	assert!(dir.join("b").exists());
+}
+
+#[test]
+fn rollback() {
+	let dir = TestDir::new("journal_rollback");
+	interrupted(&dir);
+	let journal = Unfinished::load(&dir.join("journal").join("1.ndjson")).expect("Failed to load");
+	assert!(journal.rollback().entries().is_empty());
+	assert!(dir.join("a").exists());
+	assert!(dir.join("b").exists());
+	assert!(!dir.join("a_new").exists());
+	assert!(!dir.join("journal").join("1.ndjson").exists());
+}
+
+#[test]
+fn removed_after_run() {
+	let dir = TestDir::new("journal_run");
+	fs::write(dir.join("a"), "a").expect("Failed to create test file");
+	let log = Rename::builder()
+		.file(dir.join("a"))
+		.suffix("_new")
+		.journal(dir.join("journal"))
+		.build()
+		.expect("Failed to build")
+		.rename();
+	assert!(log.entries().is_empty());
+	assert!(dir.join("a_new").exists());
+	assert_eq!(
+		fs::read_dir(dir.join("journal"))
+			.expect("Journal dir missing")
+			.count(),
+		0
+	);
+}
+
+#[test]
+fn running_and_broken() {
+	let dir = TestDir::new("journal_running");
+	let plan = interrupted(&dir);
+	fs::write(dir.join("journal").join("0.ndjson"), "broken").expect("Failed to write journal");
+	let journal = Journal::create(&dir.join("journal"), &plan).expect("Failed to create journal");
+	// Only the interrupted one, the running one is locked.
+	let found = Unfinished::find(&dir.join("journal")).expect("Failed to read journals");
+	assert_eq!(found.len(), 1);
+	assert_eq!(found[0].path, dir.join("journal").join("1.ndjson"));
+	drop(journal);
+	assert_eq!(
+		Unfinished::find(&dir.join("journal"))
+			.expect("Failed to read journals")
+			.len(),
+		2
+	);
+}
+
+#[test]
+fn swap() {
+	let dir = TestDir::new("journal_swap");
+	fs::create_dir_all(dir.join("journal")).expect("Failed to create test dir");
+	let (a, b) = (dir.join("a"), dir.join("b"));
+	fs::write(&a, "a").expect("Failed to create test file");
+	fs::write(&b, "bb").expect("Failed to create test file");
+	let plan = Rename::default().plan_swap(&a, &b).expect("Failed to plan");
+	let journal = dir.join("journal").join("1.ndjson");
+	let lines = [
+		serde_json::json!({"event": "begin", "pid": 0, "plan": plan}),
+		serde_json::json!({"event": "started", "index": 0, "operation": plan.operations[0]}),
+	]
+	.map(|l| l.to_string());
+	fs::write(&journal, lines.join("\n")).expect("Failed to write journal");
+
+	// Killed before the swap, it's run.
+	assert!(Unfinished::load(&journal)
+		.expect("Failed to load")
+		.finish(None)
+		.entries()
+		.is_empty());
+	assert_eq!(fs::read_to_string(&a).expect("Missing"), "bb");
+	assert_eq!(fs::read_to_string(&b).expect("Missing"), "a");
+
+	// Killed after the swap, it's swapped back.
+	fs::write(&journal, lines.join("\n")).expect("Failed to write journal");
+	assert!(Unfinished::load(&journal)
+		.expect("Failed to load")
+		.rollback()
+		.entries()
+		.is_empty());
+	assert_eq!(fs::read_to_string(&a).expect("Missing"), "a");
+	assert_eq!(fs::read_to_string(&b).expect("Missing"), "bb");
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::{cmp::Ordering, ffi::OsStr, fs, path::PathBuf};
+
+use common::TestDir;
+use renameplus::{natural_cmp, Error, Numbering, Rename, SortBy};
+
+#[test]
+fn natural_order() {
+	let cmp = |a: &str, b: &str| natural_cmp(OsStr::new(a), OsStr::new(b));
+	assert_eq!(cmp("img2.jpg", "img10.jpg"), Ordering::Less);
+	assert_eq!(cmp("img010.jpg", "img9.jpg"), Ordering::Greater);
+	assert_eq!(cmp("a.jpg", "B.jpg"), Ordering::Less);
+	assert_eq!(cmp("a1", "a01"), Ordering::Greater);
+}
+
+#[test]
+fn sorted_by_name() {
+	let files: Vec<PathBuf> = ["b10", "b2", "a"].iter().map(PathBuf::from).collect();
+	let numbering = Numbering {
+		start: 0,
+		step: 5,
+		sort: SortBy::Name,
+		..Default::default()
+	};
+	let numbers: Vec<u64> = numbering
+		.numbers(&files)
+		.into_iter()
+		.map(|n| n.expect("Failed to number"))
+		.collect();
+	assert_eq!(numbers, [10, 5, 0]);
+}
+
+#[test]
+fn missing_file_fails_alone() {
+	let dir = TestDir::new("numbering_missing");
+	fs::write(dir.join("big"), "big").expect("Failed to create test file");
+	fs::write(dir.join("small"), "s").expect("Failed to create test file");
+	let files = [dir.join("big"), dir.join("missing"), dir.join("small")];
+	let numbering = Numbering {
+		sort: SortBy::Size,
+		..Default::default()
+	};
+	let numbers = numbering.numbers(&files);
+	assert_eq!(numbers[0].as_ref().ok(), Some(&2));
+	assert!(matches!(
+		numbers[1].as_ref().map_err(Error::find),
+		Err(Some(Error::NotFound { .. }))
+	));
+	assert_eq!(numbers[2].as_ref().ok(), Some(&1));
+
+	let previews = Rename::builder()
+		.files(files)
+		.template("{n}".parse().expect("Invalid template"))
+		.numbering(numbering)
+		.build()
+		.expect("Failed to build")
+		.preview_results();
+	assert_eq!(previews[0].1.as_ref().ok(), Some(&Some(dir.join("2"))));
+	assert!(previews[1].1.is_err());
+}
+
+#[test]
+fn get_new_path_keeps_numbers() {
+	let dir = TestDir::new("numbering_cached");
+	for name in ["a", "b", "c"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let mut rename = Rename::builder()
+		.files([dir.join("b"), dir.join("a")])
+		.template("{n}".parse().expect("Invalid template"))
+		.numbering(Numbering {
+			sort: SortBy::Name,
+			..Default::default()
+		})
+		.build()
+		.expect("Failed to build");
+	let new = |rename: &Rename, name: &str| {
+		rename
+			.get_new_path(&dir.join(name))
+			.expect("Failed to get new path")
+	};
+	assert_eq!(new(&rename, "b"), Some(dir.join("2")));
+	assert_eq!(new(&rename, "a"), Some(dir.join("1")));
+	// Changes after the first call are numbered again.
+	rename.numbering.start = 5;
+	assert_eq!(new(&rename, "b"), Some(dir.join("6")));
+	rename.files.push(dir.join("c"));
+	assert_eq!(new(&rename, "c"), Some(dir.join("7")));
+}
@@ -0,0 +1,367 @@
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+use renameplus::{Error, OperationKind, Reason, Rename, RenamePlan};
+
+#[test]
+fn plan_and_execute() {
+	let dir = TestDir::new("plan");
+	for name in ["a.txt", "b.txt", "new_b.txt"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let rename = Rename::builder()
+		.files([dir.join("a.txt"), dir.join("b.txt")])
+		.prefix("new_")
+		.build()
+		.expect("Failed to build");
+	let plan = rename.plan().expect("Failed to plan");
+	let kinds: Vec<_> = plan.operations.iter().map(|o| o.kind).collect();
+	assert_eq!(kinds, [OperationKind::Move, OperationKind::Skip]);
+	assert_eq!(plan.operations[1].reason, Some(Reason::TargetExists));
+
+	let json = plan.to_json().expect("Failed to serialize");
+	assert_eq!(RenamePlan::from_json(&json).expect("Failed to parse"), plan);
+	let toml = plan.to_toml().expect("Failed to serialize");
+	assert_eq!(RenamePlan::from_toml(&toml).expect("Failed to parse"), plan);
+
+	// Source changed since the plan was made.
+	fs::write(dir.join("a.txt"), "changed").expect("Failed to change test file");
+	let err = plan.verify().expect_err("Change not detected");
+	assert!(matches!(
+		Error::find(&err),
+		Some(Error::SourceChanged { .. })
+	));
+
+	let plan = rename.plan().expect("Failed to plan");
+	assert!(plan.execute(false).entries().is_empty());
+	assert!(dir.join("new_a.txt").exists());
+	assert!(!dir.join("a.txt").exists());
+}
+
+#[test]
+fn collisions() {
+	let dir = TestDir::new("collisions");
+	for name in ["a b.txt", "a_b.txt", "a-b.txt", "c.txt"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let plan = Rename::builder()
+		.files(["a b.txt", "a-b.txt", "c.txt"].map(|f| dir.join(f)))
+		.replace(renameplus::Replace::parse("s/[ -]/_/r", false).expect("Invalid expression"))
+		.prefix("x_")
+		.build()
+		.expect("Failed to build")
+		.plan()
+		.expect("Failed to plan");
+	let kinds: Vec<_> = plan.operations.iter().map(|o| o.kind).collect();
+	assert_eq!(
+		kinds,
+		[
+			OperationKind::Skip,
+			OperationKind::Skip,
+			OperationKind::Move
+		]
+	);
+	let collisions = plan.collisions();
+	assert_eq!(collisions.len(), 1);
+	assert!(
+		matches!(&collisions[0], Error::Collision { sources, .. } if sources.len() == 2),
+		"{collisions:?}"
+	);
+}
+
+#[derive(Debug)]
+struct Swap;
+
+impl renameplus::Transform for Swap {
+	fn apply(
+		&self,
+		name: &mut renameplus::FileName,
+		_: &renameplus::TransformContext,
+	) -> anyhow::Result<()> {
+		name.stem = match name.stem.to_str() {
+			Some("a") => "b",
+			_ => "a",
+		}
+		.into();
+		Ok(())
+	}
+}
+
+#[test]
+fn chains_and_cycles() {
+	let dir = TestDir::new("chains");
+	for name in ["1.jpg", "2.jpg", "a.txt", "b.txt"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let renumber = Rename::builder()
+		.files([dir.join("1.jpg"), dir.join("2.jpg")])
+		.template("{n}.{ext}".parse().expect("Invalid template"))
+		.numbering(renameplus::Numbering {
+			start: 2,
+			..Default::default()
+		})
+		.build()
+		.expect("Failed to build");
+	let plan = renumber.plan().expect("Failed to plan");
+	let sources: Vec<_> = plan.operations.iter().map(|o| o.source.clone()).collect();
+	assert_eq!(sources, [dir.join("2.jpg"), dir.join("1.jpg")]);
+	assert!(renumber.rename().entries().is_empty());
+	assert_eq!(
+		fs::read_to_string(dir.join("2.jpg")).expect("Missing"),
+		"1.jpg"
+	);
+	assert_eq!(
+		fs::read_to_string(dir.join("3.jpg")).expect("Missing"),
+		"2.jpg"
+	);
+
+	let swap = Rename::builder()
+		.files([dir.join("a.txt"), dir.join("b.txt")])
+		.transform(Swap)
+		.build()
+		.expect("Failed to build");
+	let plan = swap.plan().expect("Failed to plan");
+	assert_eq!(plan.operations.len(), 3);
+	assert!(plan
+		.operations
+		.iter()
+		.all(|o| o.kind == OperationKind::Move));
+	assert!(plan.execute(false).entries().is_empty());
+	assert_eq!(
+		fs::read_to_string(dir.join("a.txt")).expect("Missing"),
+		"b.txt"
+	);
+	assert_eq!(
+		fs::read_to_string(dir.join("b.txt")).expect("Missing"),
+		"a.txt"
+	);
+	assert_eq!(fs::read_dir(&dir).expect("Failed to read dir").count(), 4);
+}
+
+/// Renames `a` onto `a_new` and `b` into a missing directory.
+#[derive(Debug)]
+struct FailSecond;
+
+impl renameplus::Transform for FailSecond {
+	fn apply(
+		&self,
+		name: &mut renameplus::FileName,
+		_: &renameplus::TransformContext,
+	) -> anyhow::Result<()> {
+		name.stem = match name.stem.to_str() {
+			Some("a") => "a_new",
+			_ => "missing/b",
+		}
+		.into();
+		Ok(())
+	}
+}
+
+#[test]
+fn undo_restores_overwritten() {
+	let dir = TestDir::new("undo_overwritten");
+	for (name, content) in [("a", "a"), ("a_new", "old"), ("b", "b")] {
+		fs::write(dir.join(name), content).expect("Failed to create test file");
+	}
+	let log = Rename::builder()
+		.files([dir.join("a"), dir.join("b")])
+		.transform(FailSecond)
+		.on_conflict(renameplus::args::OnConflict::Overwrite)
+		.undo_on_err(true)
+		.build()
+		.expect("Failed to build")
+		.rename();
+	assert!(!log.entries().is_empty());
+	assert_eq!(fs::read_to_string(dir.join("a")).expect("Missing"), "a");
+	assert_eq!(
+		fs::read_to_string(dir.join("a_new")).expect("Missing"),
+		"old"
+	);
+	// Nothing left in the staging area.
+	assert_eq!(fs::read_dir(&dir).expect("Failed to read dir").count(), 3);
+}
+
+#[test]
+fn never_overwrites_dirs() {
+	let dir = TestDir::new("overwrite_dir");
+	fs::create_dir_all(dir.join("new_a")).expect("Failed to create test dir");
+	fs::write(dir.join("new_a").join("keep"), "keep").expect("Failed to create test file");
+	fs::write(dir.join("a"), "a").expect("Failed to create test file");
+	let log = Rename::builder()
+		.file(dir.join("a"))
+		.prefix("new_")
+		.on_conflict(renameplus::args::OnConflict::Overwrite)
+		.build()
+		.expect("Failed to build")
+		.rename();
+	assert!(!log.entries().is_empty());
+	assert_eq!(fs::read_to_string(dir.join("a")).expect("Missing"), "a");
+	assert_eq!(
+		fs::read_to_string(dir.join("new_a").join("keep")).expect("Missing"),
+		"keep"
+	);
+	assert_eq!(fs::read_dir(&*dir).expect("Failed to read dir").count(), 2);
+}
+
+#[test]
+fn nested() {
+	let dir = TestDir::new("nested");
+	fs::create_dir_all(dir.join("d").join("sub")).expect("Failed to create test dir");
+	fs::write(dir.join("d").join("f.txt"), "f").expect("Failed to create test file");
+	let rename = Rename::builder()
+		.files([dir.join("d"), dir.join("d").join("f.txt")])
+		.dirs(true)
+		.suffix("_x")
+		.build()
+		.expect("Failed to build");
+	let preview = rename.preview().expect("Failed to preview");
+	assert_eq!(preview[0].1, Some(dir.join("d_x")));
+	assert_eq!(preview[1].1, Some(dir.join("d_x").join("f_x.txt")));
+	let plan = rename.plan().expect("Failed to plan");
+	assert_eq!(plan.operations[0].source, dir.join("d").join("f.txt"));
+	assert!(plan.execute(false).entries().is_empty());
+	assert!(dir.join("d_x").join("f_x.txt").exists());
+
+	// No file name, renames the directory it points to.
+	let rename = Rename::builder()
+		.file(dir.join("d_x").join("sub").join(".."))
+		.dirs(true)
+		.suffix("_y")
+		.build()
+		.expect("Failed to build");
+	let preview = rename.preview().expect("Failed to preview");
+	let canonical = dir.canonicalize().expect("Failed to resolve dir");
+	assert_eq!(preview[0].1, Some(canonical.join("d_x_y")));
+	assert!(rename.rename().entries().is_empty());
+	assert!(dir.join("d_x_y").join("f_x.txt").exists());
+	let skipped = Rename::builder()
+		.file(dir.join("d_x_y"))
+		.suffix("_z")
+		.build()
+		.expect("Failed to build")
+		.plan()
+		.expect("Failed to plan");
+	assert_eq!(skipped.operations[0].reason, Some(Reason::Directory));
+}
+
+#[cfg(unix)]
+#[test]
+fn copy_dirs() {
+	let dir = TestDir::new("copy_dirs");
+	fs::create_dir_all(dir.join("src").join("sub")).expect("Failed to create test dir");
+	fs::write(dir.join("src").join("a"), "a").expect("Failed to create test file");
+	fs::write(dir.join("src").join("sub").join("b"), "b").expect("Failed to create test file");
+	std::os::unix::fs::symlink("a", dir.join("src").join("link")).expect("Failed to link");
+	let copy = |suffix: &str| {
+		Rename::builder()
+			.file(dir.join("src"))
+			.dirs(true)
+			.copy(true)
+			.suffix(suffix)
+			.history(dir.join("history"))
+	};
+	let rename = |builder: renameplus::RenameBuilder| {
+		builder
+			.build()
+			.expect("Failed to build")
+			.rename()
+			.entries()
+			.is_empty()
+	};
+	assert!(rename(copy("_copy")));
+	assert!(dir
+		.join("src_copy/link")
+		.symlink_metadata()
+		.expect("Missing")
+		.is_symlink());
+	assert_eq!(
+		fs::read_to_string(dir.join("src_copy/sub/b")).expect("Missing"),
+		"b"
+	);
+	assert!(rename(copy("_real").follow_symlinks(true)));
+	assert!(dir
+		.join("src_real/link")
+		.symlink_metadata()
+		.expect("Missing")
+		.is_file());
+
+	// Existing directory, the conflict policy applies to its contents.
+	fs::create_dir(dir.join("src_merge")).expect("Failed to create test dir");
+	fs::write(dir.join("src_merge").join("a"), "old").expect("Failed to create test file");
+	fs::write(dir.join("src_merge").join("other"), "other").expect("Failed to create test file");
+	let plan = copy("_merge")
+		.build()
+		.expect("Failed to build")
+		.plan()
+		.expect("Failed to plan");
+	assert_eq!(plan.operations[0].reason, Some(Reason::Merge));
+	assert!(rename(copy("_merge")));
+	assert_eq!(
+		fs::read_to_string(dir.join("src_merge/a")).expect("Missing"),
+		"old"
+	);
+	assert!(dir.join("src_merge/sub/b").exists());
+	let history = renameplus::history::History::new(dir.join("history"));
+	assert!(history.undo(None, false).entries().is_empty());
+	let mut left: Vec<_> = fs::read_dir(dir.join("src_merge"))
+		.expect("Failed to read dir")
+		.map(|e| e.expect("Failed to read dir").file_name())
+		.collect();
+	left.sort();
+	assert_eq!(left, ["a", "other"]);
+	assert!(rename(
+		copy("_merge").on_conflict(renameplus::args::OnConflict::Overwrite)
+	));
+	assert_eq!(
+		fs::read_to_string(dir.join("src_merge/a")).expect("Missing"),
+		"a"
+	);
+	assert!(dir.join("src_merge/other").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn links() {
+	use renameplus::args::LinkKind;
+	let dir = TestDir::new("links");
+	fs::create_dir_all(dir.join("library").join("music")).expect("Failed to create test dir");
+	fs::create_dir_all(dir.join("view")).expect("Failed to create test dir");
+	let song = dir.join("library").join("music").join("song.mp3");
+	fs::write(&song, "song").expect("Failed to create test file");
+	let link = |kind: LinkKind, prefix: &str| {
+		Rename::builder()
+			.file(&song)
+			.prefix(prefix)
+			.link(kind)
+			.output_dir(dir.join("view"))
+			.history(dir.join("history"))
+			.build()
+			.expect("Failed to build")
+			.rename()
+	};
+	assert!(link(LinkKind::Relative, "rel_").entries().is_empty());
+	assert_eq!(
+		fs::read_link(dir.join("view/rel_song.mp3")).expect("No symlink"),
+		std::path::Path::new("../library/music/song.mp3")
+	);
+	assert!(link(LinkKind::Symbolic, "abs_").entries().is_empty());
+	assert!(fs::read_link(dir.join("view/abs_song.mp3"))
+		.expect("No symlink")
+		.is_absolute());
+	assert!(link(LinkKind::Hard, "hard_").entries().is_empty());
+	assert_eq!(
+		fs::read_to_string(dir.join("view/hard_song.mp3")).expect("Missing"),
+		"song"
+	);
+	assert!(song.exists());
+
+	// Taken targets follow the conflict policy, undo removes only the link.
+	assert!(link(LinkKind::Hard, "hard_").entries().is_empty());
+	let history = renameplus::history::History::new(dir.join("history"));
+	assert_eq!(history.batches().expect("Failed to read history").len(), 3);
+	assert!(history.undo(None, false).entries().is_empty());
+	assert!(!dir.join("view/hard_song.mp3").exists());
+	assert!(song.exists());
+}
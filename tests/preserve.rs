@@ -0,0 +1,39 @@
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+use filetime::FileTime;
+use renameplus::{preserve::Preserve, Rename};
+
+#[test]
+fn keeps_timestamps() {
+	let dir = TestDir::new("preserve");
+	let old = FileTime::from_unix_time(1_000_000_000, 0);
+	for name in ["a", "b"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+		filetime::set_file_mtime(dir.join(name), old).expect("Failed to set mtime");
+	}
+	let copy = |file: &str, preserve: Preserve| {
+		Rename::builder()
+			.file(dir.join(file))
+			.suffix("_copy")
+			.copy(true)
+			.preserve(preserve)
+			.build()
+			.expect("Failed to build")
+			.rename()
+	};
+	let mtime = |file: &str| {
+		FileTime::from_last_modification_time(&fs::metadata(dir.join(file)).expect("Missing"))
+	};
+	assert!(copy("a", Preserve::default()).entries().is_empty());
+	assert_ne!(mtime("a_copy"), old);
+	let timestamps = Preserve {
+		timestamps: true,
+		..Default::default()
+	};
+	assert!(copy("b", timestamps).entries().is_empty());
+	assert_eq!(mtime("b_copy"), old);
+	assert_eq!(mtime("b"), old);
+}
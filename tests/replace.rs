@@ -0,0 +1,85 @@
+use std::ffi::OsString;
+
+use renameplus::{Occurrence, Replace};
+
+fn apply(expr: &str, name: &str) -> String {
+	let mut name = OsString::from(name);
+	Replace::parse(expr, false)
+		.expect("Failed to parse expression")
+		.apply(&mut name)
+		.expect("Failed to apply expression");
+	name.into_string().expect("Not unicode")
+}
+
+#[test]
+fn simple() {
+	assert_eq!(apply("a/b", "aXa"), "bXb");
+	assert_eq!(apply("a\\/b/c", "a/b.txt"), "c.txt");
+	assert!(Replace::parse("a/b/c", false).is_err());
+}
+
+#[test]
+fn sed() {
+	assert_eq!(apply("s|a|b|", "aXa"), "bXa");
+	assert_eq!(apply("s|a|b|g", "aXa"), "bXb");
+	assert_eq!(apply("s|a|b|l", "aXaXa"), "aXaXb");
+	assert_eq!(apply("s|a|b|2", "aXaXa"), "aXbXa");
+	assert_eq!(apply("s|A|b|gi", "aXA"), "bXb");
+	assert_eq!(apply("s/a\\/b/c/", "a/b"), "c");
+	assert_eq!(
+		apply(r"s/IMG_(\d{4})(\d{2})(\d{2})/\1-\2-\3/r", "IMG_20230512"),
+		"2023-05-12"
+	);
+	let expr = Replace::parse("s|a|b|", false).expect("Failed to parse expression");
+	assert_eq!(expr.occurrence, Occurrence::First);
+}
+
+#[test]
+fn sed_errors() {
+	for expr in [
+		"s|a|b",
+		"s|a|b|q",
+		"s|a|b|gl",
+		"s|a|b|0",
+		"s||b|",
+		"s|a\\q|b|",
+	] {
+		assert!(Replace::parse(expr, false).is_err(), "{expr}");
+	}
+	let err = Replace::parse("s|a|b|gq", false)
+		.expect_err("Flag should be rejected")
+		.to_string();
+	assert!(err.ends_with("\n  s|a|b|gq\n         ^"), "{err}");
+}
+
+#[test]
+fn regex_groups() {
+	let mut name = OsString::from("IMG_20230512");
+	Replace::new_regex(r"IMG_(?P<y>\d{4})(\d{2})(\d{2})", "${y}-$2-$3")
+		.apply(&mut name)
+		.expect("Failed to apply regex");
+	assert_eq!(name, "2023-05-12");
+}
+
+#[test]
+fn reused() {
+	let replace = Replace::parse("s|a|b|gi", false).expect("Failed to parse expression");
+	assert_eq!(replace, Replace::parse("s|a|b|gi", false).expect("Failed"));
+	for (name, expected) in [("aXA", "bXb"), ("Xa", "Xb"), ("X", "X")] {
+		let mut name = OsString::from(name);
+		replace
+			.apply(&mut name)
+			.expect("Failed to apply expression");
+		assert_eq!(name, expected);
+	}
+	assert_eq!(replace, Replace::parse("s|a|b|gi", false).expect("Failed"));
+
+	// A changed copy doesn't use the regex of the old search term.
+	let mut changed = replace.clone();
+	changed.search = "x".to_string();
+	let mut name = OsString::from("aX");
+	changed
+		.apply(&mut name)
+		.expect("Failed to apply expression");
+	assert_eq!(name, "ab");
+}
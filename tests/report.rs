@@ -0,0 +1,168 @@
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+use renameplus::{
+	args::{LinkKind, OutputFormat},
+	report::{write_reports, Report, Status},
+	ErrorCategory, OperationKind, Rename,
+};
+
+#[test]
+fn reports() {
+	let dir = TestDir::new("report");
+	fs::write(dir.join("a.txt"), "a").expect("Failed to create test file");
+	let builder = Rename::builder()
+		.files([dir.join("a.txt"), dir.join("missing.txt")])
+		.prefix("new_");
+
+	let mut planned = vec![];
+	let rename = builder.clone().dry(true).build().expect("Failed to build");
+	let _ = rename.rename_reported(&mut planned);
+	let status: Vec<_> = planned.iter().map(|r| r.status).collect();
+	assert_eq!(status, [Status::Planned, Status::Failed]);
+	assert_eq!(planned[1].category, Some(ErrorCategory::NotFound));
+	assert!(dir.join("a.txt").exists());
+
+	let mut done = vec![];
+	let rename = builder.build().expect("Failed to build");
+	let _ = rename.rename_reported(&mut done);
+	assert_eq!(done[0].status, Status::Done);
+	assert_eq!(done[0].action, OperationKind::Move);
+	assert_eq!(done[0].target, Some(dir.join("new_a.txt")));
+
+	let mut ndjson = vec![];
+	write_reports(&done, OutputFormat::Ndjson, &mut ndjson).expect("Failed to write");
+	let parsed: Vec<Report> = String::from_utf8(ndjson)
+		.expect("Not utf8")
+		.lines()
+		.map(|l| serde_json::from_str(l).expect("Failed to parse"))
+		.collect();
+	assert_eq!(parsed, done);
+}
+
+#[test]
+fn fragile_collisions() {
+	let dir = TestDir::new("report_collisions");
+	for name in ["a.txt", "b.txt"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	let mut reports = vec![];
+	let log = Rename::builder()
+		.files([dir.join("a.txt"), dir.join("b.txt")])
+		.template("same.{ext}".parse().expect("Invalid template"))
+		.fragile(true)
+		.build()
+		.expect("Failed to build")
+		.rename_reported(&mut reports);
+	assert!(!log.entries().is_empty());
+	assert_eq!(reports.len(), 2);
+	for report in reports {
+		assert_eq!(report.status, Status::Failed);
+		assert_eq!(report.category, Some(ErrorCategory::Conflict));
+		assert_eq!(report.target, Some(dir.join("same.txt")));
+	}
+	assert!(dir.join("a.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8() {
+	use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+	let report = Report {
+		source: PathBuf::from(OsStr::from_bytes(b"caf\xe9\t.txt")),
+		target: Some(PathBuf::from("cafe.txt")),
+		action: OperationKind::Move,
+		status: Status::Planned,
+		reason: None,
+		category: None,
+		error: None,
+	};
+	let mut json = vec![];
+	write_reports(std::slice::from_ref(&report), OutputFormat::Json, &mut json)
+		.expect("Failed to write");
+	let parsed: Vec<Report> = serde_json::from_slice(&json).expect("Failed to parse");
+	assert_eq!(parsed, std::slice::from_ref(&report));
+
+	let mut tsv = vec![];
+	write_reports(&[report], OutputFormat::Tsv, &mut tsv).expect("Failed to write");
+	assert_eq!(
+		String::from_utf8(tsv).expect("Not utf8"),
+		"source\ttarget\taction\tstatus\tcategory\terror\n\
+		 caf\\xe9\\t.txt\tcafe.txt\tmove\tplanned\t\t\n"
+	);
+}
+
+#[test]
+fn every_operation() {
+	let dir = TestDir::new("report_every");
+	for name in ["1", "2", "a"] {
+		fs::write(dir.join(name), name).expect("Failed to create test file");
+	}
+	// A cycle is one row per file.
+	let mut reports = vec![];
+	let rename = Rename::builder()
+		.files([dir.join("2"), dir.join("1")])
+		.template("{n}".parse().expect("Invalid template"))
+		.build()
+		.expect("Failed to build");
+	assert!(rename.rename_reported(&mut reports).entries().is_empty());
+	let rows: Vec<_> = reports
+		.iter()
+		.map(|r| (r.source.clone(), r.target.clone(), r.status))
+		.collect();
+	assert_eq!(
+		rows,
+		[
+			(dir.join("1"), Some(dir.join("2")), Status::Done),
+			(dir.join("2"), Some(dir.join("1")), Status::Done),
+		]
+	);
+
+	// Operations after a failure with --fragile are skipped.
+	let mut reports = vec![];
+	let _ = Rename::builder()
+		.files([dir.join("missing"), dir.join("a")])
+		.suffix("_new")
+		.fragile(true)
+		.build()
+		.expect("Failed to build")
+		.rename_reported(&mut reports);
+	let status: Vec<_> = reports.iter().map(|r| r.status).collect();
+	assert_eq!(status, [Status::Failed, Status::Skipped]);
+	assert!(dir.join("a").exists());
+
+	// Nothing is run for an outdated plan, every operation failed.
+	let rename = Rename::builder()
+		.files([dir.join("1"), dir.join("a")])
+		.suffix("_new")
+		.build()
+		.expect("Failed to build");
+	let plan = rename.plan().expect("Failed to plan");
+	fs::write(dir.join("a"), "changed").expect("Failed to change test file");
+	let mut reports = vec![];
+	let _ = plan.execute_reported(false, |_| unreachable!(), &mut reports);
+	let status: Vec<_> = reports.iter().map(|r| r.status).collect();
+	assert_eq!(status, [Status::Failed, Status::Failed]);
+	assert!(dir.join("1").exists());
+}
+
+#[test]
+fn action_is_a_string() {
+	let report = Report {
+		source: "a".into(),
+		target: Some("b".into()),
+		action: OperationKind::Link(LinkKind::Hard),
+		status: Status::Done,
+		reason: None,
+		category: None,
+		error: None,
+	};
+	let json = serde_json::to_value(&report).expect("Failed to serialize");
+	assert_eq!(json["action"], "link-hard");
+	assert_eq!(
+		serde_json::from_value::<Report>(json).expect("Failed to parse"),
+		report
+	);
+}
@@ -0,0 +1,71 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+use common::TestDir;
+use renameplus::{args::SymlinkPolicy, history::History, Rename};
+
+#[test]
+fn policy() {
+	let dir = TestDir::new("symlink_policy");
+	fs::write(dir.join("file"), "file").expect("Failed to create test file");
+	symlink("file", dir.join("link")).expect("Failed to link");
+	symlink("missing", dir.join("dangling")).expect("Failed to link");
+	let rename = |file: &str, policy: SymlinkPolicy| {
+		Rename::builder()
+			.file(dir.join(file))
+			.suffix("_new")
+			.symlinks(policy)
+			.build()
+			.expect("Failed to build")
+			.rename()
+	};
+	assert!(rename("dangling", SymlinkPolicy::Link).entries().is_empty());
+	assert!(dir.join("dangling_new").symlink_metadata().is_ok());
+	assert!(!rename("dangling_new", SymlinkPolicy::Target)
+		.entries()
+		.is_empty());
+	assert!(rename("link", SymlinkPolicy::Target).entries().is_empty());
+	assert!(dir.join("file_new").is_file());
+	assert!(dir.join("link").symlink_metadata().is_ok());
+}
+
+#[test]
+fn fix_links() {
+	let dir = TestDir::new("fix_links");
+	fs::create_dir_all(dir.join("data")).expect("Failed to create test dir");
+	fs::create_dir_all(dir.join("links")).expect("Failed to create test dir");
+	fs::write(dir.join("data").join("a"), "a").expect("Failed to create test file");
+	symlink("../data/a", dir.join("links").join("relative")).expect("Failed to link");
+	symlink(
+		dir.join("data").join("a"),
+		dir.join("links").join("absolute"),
+	)
+	.expect("Failed to link");
+	symlink("../data/other", dir.join("links").join("other")).expect("Failed to link");
+	assert!(Rename::builder()
+		.file(dir.join("data").join("a"))
+		.suffix("_new")
+		.fix_links(dir.join("links"))
+		.history(dir.join("history"))
+		.build()
+		.expect("Failed to build")
+		.rename()
+		.entries()
+		.is_empty());
+	let target = |link: &str| fs::read_link(dir.join("links").join(link)).expect("No symlink");
+	assert_eq!(target("relative"), Path::new("../data/a_new"));
+	assert_eq!(target("absolute"), dir.join("data").join("a_new"));
+	assert_eq!(target("other"), Path::new("../data/other"));
+	assert_eq!(
+		fs::read_to_string(dir.join("links").join("relative")).expect("Broken link"),
+		"a"
+	);
+
+	let history = History::new(dir.join("history"));
+	assert!(history.undo(None, false).entries().is_empty());
+	assert_eq!(target("relative"), Path::new("../data/a"));
+	assert_eq!(target("absolute"), dir.join("data").join("a"));
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use renameplus::{Template, TemplateContext};
+
+fn render(template: &str, path: &str, n: u64) -> String {
+	Template::parse(template)
+		.expect("Failed to parse template")
+		.render(&TemplateContext {
+			path: Path::new(path),
+			name: Path::new(path).file_name().expect("No file name"),
+			n,
+			pad: 0,
+		})
+		.expect("Failed to render template")
+		.into_string()
+		.expect("Not unicode")
+}
+
+#[test]
+fn placeholders() {
+	assert_eq!(render("{stem}_{n:3}.{ext}", "dir/a.txt", 7), "a_007.txt");
+	assert_eq!(render("{{{name}}}", "a.txt", 1), "{a.txt}");
+	assert_eq!(render("{stem[0:3]|upper}", "abcdef.txt", 1), "ABC");
+	assert_eq!(render("{stem[-2:]}", "abcdef.txt", 1), "ef");
+}
+
+#[test]
+fn errors() {
+	for template in [
+		"{stem",
+		"stem}",
+		"{foo}",
+		"{stem:x}",
+		"{stem|foo}",
+		"{n:x}",
+		"{stem[1]}",
+		"{mtime:%Q}",
+	] {
+		assert!(Template::parse(template).is_err(), "{template}");
+	}
+}
+
+#[test]
+fn no_file_name() {
+	for template in [".", "..", "{stem}/x", "a/."] {
+		let rendered = Template::parse(template)
+			.expect("Failed to parse template")
+			.render(&TemplateContext {
+				path: Path::new("a.txt"),
+				name: Path::new("a.txt").as_os_str(),
+				n: 1,
+				pad: 0,
+			});
+		assert!(rendered.is_err(), "{template}");
+	}
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use anyhow::Result;
+use renameplus::{Config, FileName, Step, Transform, TransformContext};
+
+#[derive(Debug)]
+struct Reverse;
+
+impl Transform for Reverse {
+	fn apply(&self, name: &mut FileName, _: &TransformContext) -> Result<()> {
+		name.stem = name
+			.stem
+			.to_string_lossy()
+			.chars()
+			.rev()
+			.collect::<String>()
+			.into();
+		Ok(())
+	}
+}
+
+fn apply(steps: &[Step], config: &Config, path: &str) -> String {
+	let mut name = FileName::from_path(Path::new(path)).expect("No file name");
+	let ctx = TransformContext {
+		path: Path::new(path),
+		n: 1,
+		pad: 0,
+		config,
+	};
+	for step in steps {
+		step.apply(&mut name, &ctx).expect("Failed to apply step");
+	}
+	name.joined().into_string().expect("Not unicode")
+}
+
+#[test]
+fn order() {
+	let config = Config::default();
+	let prefix = Step::Prefix {
+		text: "a_".to_string(),
+	};
+	let template = Step::Template {
+		template: "{stem|upper}".parse().expect("Invalid template"),
+	};
+	assert_eq!(
+		apply(&[prefix.clone(), template.clone()], &config, "x.txt"),
+		"A_X"
+	);
+	assert_eq!(apply(&[template, prefix], &config, "x.txt"), "a_X");
+	assert_eq!(
+		apply(&[Step::custom(Reverse)], &config, "abc.txt"),
+		"cba.txt"
+	);
+}
+
+#[test]
+fn pipelines() {
+	let config: Config = toml::from_str(
+		r#"
+		[[pipelines.photos]]
+		type = "replace"
+		search = "IMG_"
+		replace = ""
+
+		[[pipelines.photos]]
+		type = "case"
+		case = "snake"
+
+		[[pipelines.photos]]
+		type = "suffix"
+		text = "_edited"
+		"#,
+	)
+	.expect("Failed to parse config");
+	assert_eq!(
+		apply(&config.pipelines["photos"], &config, "IMG_Beach Day.jpg"),
+		"beach_day_edited.jpg"
+	);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::TestDir;
+use renameplus::{Rename, Walk};
+
+fn tree(name: &str) -> TestDir {
+	let dir = TestDir::new(name);
+	fs::create_dir_all(dir.join("sub").join("deep")).expect("Failed to create test dir");
+	for file in [
+		"a.jpg",
+		"b.txt",
+		".hidden.jpg",
+		"ignored.jpg",
+		"sub/c.jpg",
+		"sub/deep/d.jpg",
+	] {
+		fs::write(dir.join(file), file).expect("Failed to create test file");
+	}
+	fs::write(dir.join(".ignore"), "ignored.jpg").expect("Failed to create test file");
+	dir
+}
+
+#[test]
+fn filters() {
+	let dir = tree("walk");
+	let files = |walk: Walk| -> Vec<PathBuf> {
+		walk.files(std::slice::from_ref(&dir), false)
+			.expect("Failed to walk")
+			.into_iter()
+			.map(|p| p.strip_prefix(&dir).expect("Outside of dir").to_owned())
+			.collect()
+	};
+	let jpg = Walk {
+		include: vec!["*.jpg".into()],
+		..Default::default()
+	};
+	let all = ["a.jpg", "ignored.jpg", "sub/c.jpg", "sub/deep/d.jpg"].map(PathBuf::from);
+	assert_eq!(files(jpg.clone()), all);
+	assert_eq!(
+		files(Walk {
+			gitignore: true,
+			..jpg.clone()
+		}),
+		["a.jpg", "sub/c.jpg", "sub/deep/d.jpg"].map(PathBuf::from)
+	);
+	assert_eq!(
+		files(Walk {
+			hidden: true,
+			..jpg.clone()
+		})[0],
+		PathBuf::from(".hidden.jpg")
+	);
+	assert_eq!(
+		files(Walk {
+			max_depth: Some(1),
+			..jpg.clone()
+		}),
+		["a.jpg", "ignored.jpg"].map(PathBuf::from)
+	);
+	assert_eq!(
+		files(Walk {
+			exclude: vec!["deep".into()],
+			..jpg
+		}),
+		["a.jpg", "ignored.jpg", "sub/c.jpg"].map(PathBuf::from)
+	);
+}
+
+#[test]
+fn deepest_first() {
+	let dir = tree("walk_dirs");
+	let files = Walk::default()
+		.files(std::slice::from_ref(&dir), true)
+		.expect("Failed to walk");
+	let position = |p: PathBuf| files.iter().position(|f| *f == p).expect("Missing");
+	assert!(position(dir.join("sub/deep/d.jpg")) < position(dir.join("sub/deep")));
+	assert!(position(dir.join("sub/deep")) < position(dir.join("sub")));
+	assert_eq!(files.last(), Some(&*dir));
+
+	let log = Rename::builder()
+		.file(dir.join("sub"))
+		.recursive(Walk::default())
+		.dirs(true)
+		.suffix("_x")
+		.build()
+		.expect("Failed to build")
+		.rename();
+	assert!(log.entries().is_empty());
+	assert!(dir.join("sub_x/deep_x/d_x.jpg").exists());
+	assert!(dir.join("sub_x/c_x.jpg").exists());
+}